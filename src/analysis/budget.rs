@@ -0,0 +1,241 @@
+use super::{diff::normalize_path, filter::PathPattern, Analysis};
+use serde::Deserialize;
+use std::{collections::BTreeMap, path::Path};
+
+/// The budget file as it is written by the user, e.g.:
+///
+/// ```toml
+/// max_files = 50
+/// max_read_bytes = 1048576
+/// forbidden = ["/home/*", "regex:^/root/"]
+///
+/// [max_opens]
+/// "/etc/*" = 10
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BudgetFile {
+    max_files: Option<usize>,
+    max_read_bytes: Option<usize>,
+    max_write_bytes: Option<usize>,
+    #[serde(default)]
+    forbidden: Vec<String>,
+    #[serde(default)]
+    max_opens: BTreeMap<String, usize>,
+}
+
+/// Limits the I/O of a program is checked against.
+#[derive(Debug, Default)]
+pub struct Budget {
+    /// Maximum number of distinct files that are opened.
+    pub max_files: Option<usize>,
+    /// Maximum number of bytes read from all files.
+    pub max_read_bytes: Option<usize>,
+    /// Maximum number of bytes written to all files.
+    pub max_write_bytes: Option<usize>,
+    /// Paths that must not be opened, not even unsuccessfully.
+    pub forbidden: Vec<(String, PathPattern)>,
+    /// Maximum number of opens of all paths matching the pattern.
+    pub max_opens: Vec<(String, PathPattern, usize)>,
+}
+
+impl Budget {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        Self::parse(&content).map_err(|e| anyhow::anyhow!("{}: {e}", path.display()))
+    }
+
+    pub fn parse(content: &str) -> anyhow::Result<Self> {
+        let file: BudgetFile = toml::from_str(content)?;
+
+        Ok(Self {
+            max_files: file.max_files,
+            max_read_bytes: file.max_read_bytes,
+            max_write_bytes: file.max_write_bytes,
+            forbidden: file
+                .forbidden
+                .into_iter()
+                .map(|p| Ok((p.clone(), PathPattern::parse(&p)?)))
+                .collect::<anyhow::Result<_>>()?,
+            max_opens: file
+                .max_opens
+                .into_iter()
+                .map(|(p, max)| Ok((p.clone(), PathPattern::parse(&p)?, max)))
+                .collect::<anyhow::Result<_>>()?,
+        })
+    }
+}
+
+/// A limit of the budget that was exceeded.
+#[derive(Debug, PartialEq)]
+#[cfg_attr(test, derive(serde::Serialize))]
+pub struct Violation {
+    pub rule: String,
+    pub limit: usize,
+    pub actual: usize,
+    /// The paths that contributed to the violation.
+    pub paths: Vec<String>,
+}
+
+impl Analysis {
+    /// Returns the limits of the budget that are exceeded.
+    ///
+    /// The paths are reported in the form of [`Analysis::file_stats`], i.e.
+    /// with `/proc/<pid>/`, but the patterns also match the traced paths (e.g.
+    /// `/proc/[0-9]*/maps`).
+    pub fn check_budget(&self, budget: &Budget) -> Vec<Violation> {
+        let files = self.file_stats();
+        let mut traced = BTreeMap::<String, Vec<&str>>::new();
+        for thread in self.threads() {
+            let paths = thread
+                .files()
+                .keys()
+                .chain(thread.failed_opens().keys())
+                .map(String::as_str)
+                .chain(thread.leaks().iter().map(|s| s.path.as_str()));
+            for path in paths {
+                traced.entry(normalize_path(path)).or_default().push(path);
+            }
+        }
+        let matches = |pattern: &PathPattern, path: &str| {
+            pattern.matches(path)
+                || traced
+                    .get(path)
+                    .is_some_and(|paths| paths.iter().any(|p| pattern.matches(p)))
+        };
+        let opened = files
+            .iter()
+            .filter(|(_, stats)| stats.opens > 0)
+            .collect::<Vec<_>>();
+
+        let mut violations = Vec::new();
+        let mut check = |rule: String, limit: usize, actual: usize, paths: Vec<&String>| {
+            if actual > limit {
+                violations.push(Violation {
+                    rule,
+                    limit,
+                    actual,
+                    paths: paths.into_iter().cloned().collect(),
+                });
+            }
+        };
+
+        if let Some(limit) = budget.max_files {
+            check("max_files".into(), limit, opened.len(), Vec::new());
+        }
+        if let Some(limit) = budget.max_read_bytes {
+            let bytes = files.values().map(|s| s.read_bytes).sum();
+            check("max_read_bytes".into(), limit, bytes, Vec::new());
+        }
+        if let Some(limit) = budget.max_write_bytes {
+            let bytes = files.values().map(|s| s.write_bytes).sum();
+            check("max_write_bytes".into(), limit, bytes, Vec::new());
+        }
+
+        for (name, pattern, limit) in &budget.max_opens {
+            let matched = opened
+                .iter()
+                .filter(|(path, _)| matches(pattern, path))
+                .collect::<Vec<_>>();
+            let opens = matched.iter().map(|(_, stats)| stats.opens).sum();
+            let paths = matched.into_iter().map(|(path, _)| *path).collect();
+            check(format!("max_opens \"{name}\""), *limit, opens, paths);
+        }
+
+        for (name, pattern) in &budget.forbidden {
+            let matched = files
+                .iter()
+                .filter(|(path, _)| matches(pattern, path))
+                .collect::<Vec<_>>();
+            let accesses = matched.iter().map(|(_, s)| s.opens + s.failures).sum();
+            let paths = matched.into_iter().map(|(path, _)| path).collect();
+            check(format!("forbidden \"{name}\""), 0, accesses, paths);
+        }
+
+        violations
+    }
+
+    /// Prints the violations of the budget and returns how many there are.
+    pub fn print_budget_check(&self, budget: &Budget) -> usize {
+        use termimad::print_inline as mdprintln;
+
+        let violations = self.check_budget(budget);
+        if violations.is_empty() {
+            mdprintln("\n# **I/O budget: ok**\n\n");
+            return 0;
+        }
+
+        let mut table = comfy_table::Table::new();
+        table.set_header(vec!["Rule", "Limit", "Actual", "Paths"]);
+        for violation in &violations {
+            table.add_row(vec![
+                violation.rule.clone(),
+                violation.limit.to_string(),
+                violation.actual.to_string(),
+                violation.paths.join("\n"),
+            ]);
+        }
+
+        mdprintln(&format!(
+            "\n# **I/O budget: {} violations**\n\n",
+            violations.len()
+        ));
+        println!("{table}");
+
+        violations.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{analysis::analyze, tracer::BpfTracer};
+
+    #[test]
+    fn test_check_budget_threaded() {
+        let raw_trace = include_str!("../../data/threaded.txt");
+        let analysis = Analysis::new(BpfTracer::parse_trace(raw_trace).unwrap());
+
+        let budget = Budget::parse(
+            &[
+                "max_files = 5",
+                "max_read_bytes = 10000",
+                "forbidden = [\"/etc/shadow\", \"regex:^/proc/\"]",
+                "",
+                "[max_opens]",
+                "\"/etc/*\" = 12",
+                "\"/etc/passwd\" = 3",
+            ]
+            .join("\n"),
+        )
+        .unwrap();
+
+        insta::assert_json_snapshot!(analysis.check_budget(&budget));
+    }
+
+    #[test]
+    fn test_forbidden_proc_path() {
+        let analysis = analyze(&[
+            "Attaching 13 probes...",
+            "1000;10;10;execve;/bin/app",
+            "1100;10;10;openat;4294967196;/proc/10/maps;524288",
+            "1200;10;10;openat_exit;3",
+            "1300;10;10;close;3",
+            "1400;10;10;close_exit;0",
+        ]);
+
+        for pattern in ["/proc/[0-9]*/maps", "/proc/<pid>/maps"] {
+            let budget = Budget::parse(&format!("forbidden = [\"{pattern}\"]")).unwrap();
+            let violations = analysis.check_budget(&budget);
+            assert_eq!(violations.len(), 1, "{pattern}");
+            assert_eq!(violations[0].paths, ["/proc/<pid>/maps"]);
+        }
+    }
+
+    #[test]
+    fn test_invalid_budget() {
+        assert!(Budget::parse("max_file = 5").is_err());
+        assert!(Budget::parse("forbidden = [\"[\"]").is_err());
+        assert!(Budget::parse("").unwrap().max_files.is_none());
+    }
+}
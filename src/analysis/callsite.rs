@@ -0,0 +1,259 @@
+use super::{file::FileEvent, Analysis};
+use crate::{stack::Frame, syscall::tid_t};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Number of frames (from the syscall up) that are printed per call site.
+const MAX_FRAMES: usize = 8;
+
+/// Number of paths that are printed per call site.
+const MAX_PATHS: usize = 3;
+
+/// The opens, reads and writes that were issued from the same user-space
+/// stack.
+#[derive(Debug, Default, PartialEq)]
+#[cfg_attr(test, derive(serde::Serialize))]
+pub struct CallSite {
+    pub stack: Vec<Frame>,
+    pub opens: usize,
+    pub reads: usize,
+    pub writes: usize,
+    /// Bytes transferred by the reads and writes.
+    pub bytes: usize,
+    pub paths: BTreeSet<String>,
+    pub threads: BTreeSet<tid_t>,
+}
+
+impl CallSite {
+    pub const fn syscalls(&self) -> usize {
+        self.opens + self.reads + self.writes
+    }
+}
+
+impl Analysis {
+    /// Groups the sessions and events with a recorded stack by their stack,
+    /// the call sites with the most syscalls first.
+    pub fn call_sites(&self) -> Vec<CallSite> {
+        fn entry<'a, 'b>(
+            sites: &'b mut BTreeMap<&'a [Frame], CallSite>, stack: &'a [Frame], tid: tid_t,
+            path: &str,
+        ) -> &'b mut CallSite {
+            let site = sites.entry(stack).or_insert_with(|| CallSite {
+                stack: stack.to_vec(),
+                ..Default::default()
+            });
+            site.paths.insert(path.to_string());
+            site.threads.insert(tid);
+            site
+        }
+
+        let mut sites = BTreeMap::new();
+        for thread in self.threads() {
+            for (path, info) in thread.files() {
+                for session in &info.sessions {
+                    if let Some(stack) = &session.stack {
+                        entry(&mut sites, stack, thread.tid(), path).opens += 1;
+                    }
+
+                    for event in &session.events {
+                        match event {
+                            FileEvent::Read {
+                                stack: Some(stack),
+                                bytes,
+                                ..
+                            } => {
+                                let site = entry(&mut sites, stack, thread.tid(), path);
+                                site.reads += 1;
+                                site.bytes += bytes;
+                            }
+                            FileEvent::Write {
+                                stack: Some(stack),
+                                bytes,
+                                ..
+                            } => {
+                                let site = entry(&mut sites, stack, thread.tid(), path);
+                                site.writes += 1;
+                                site.bytes += bytes;
+                            }
+                            // Transfers are recorded with both files, so each
+                            // end counts once as a read or write
+                            FileEvent::Transfer {
+                                stack: Some(stack),
+                                bytes,
+                                is_source,
+                                ..
+                            } => {
+                                let site = entry(&mut sites, stack, thread.tid(), path);
+                                if *is_source {
+                                    site.reads += 1;
+                                } else {
+                                    site.writes += 1;
+                                }
+                                site.bytes += bytes;
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut sites = sites.into_values().collect::<Vec<_>>();
+        sites.sort_by(|a, b| {
+            b.syscalls()
+                .cmp(&a.syscalls())
+                .then_with(|| b.bytes.cmp(&a.bytes))
+                .then_with(|| a.stack.cmp(&b.stack))
+        });
+        sites
+    }
+
+    /// Prints the `top` call sites. Nothing is printed if no stacks were
+    /// recorded (see `--stacks`).
+    pub fn print_call_sites(&self, top: usize) {
+        use termimad::print_inline as mdprintln;
+
+        let sites = self.call_sites();
+        if sites.is_empty() {
+            return;
+        }
+
+        let mut table = comfy_table::Table::new();
+        table.set_header(vec![
+            "Call site",
+            "Opens",
+            "Reads",
+            "Writes",
+            "Bytes",
+            "Threads",
+            "Files",
+        ]);
+        for site in sites.iter().take(top) {
+            let mut stack = site
+                .stack
+                .iter()
+                .take(MAX_FRAMES)
+                .map(|frame| frame.to_string())
+                .collect::<Vec<_>>();
+            if site.stack.len() > MAX_FRAMES {
+                stack.push(format!("... {} more", site.stack.len() - MAX_FRAMES));
+            }
+
+            let mut paths = site
+                .paths
+                .iter()
+                .take(MAX_PATHS)
+                .cloned()
+                .collect::<Vec<_>>();
+            if site.paths.len() > MAX_PATHS {
+                paths.push(format!("... {} more", site.paths.len() - MAX_PATHS));
+            }
+
+            table.add_row(vec![
+                stack.join("\n"),
+                site.opens.to_string(),
+                site.reads.to_string(),
+                site.writes.to_string(),
+                site.bytes.to_string(),
+                site.threads.len().to_string(),
+                paths.join("\n"),
+            ]);
+        }
+
+        mdprintln(&format!(
+            "\n# **Call sites: top {} of {} stacks**\n\n",
+            top.min(sites.len()),
+            sites.len()
+        ));
+        println!("{table}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::analyze;
+
+    #[test]
+    fn test_call_sites() {
+        let config = [
+            "\t7f0000001000 __libc_open64+67 (/usr/lib/libc.so.6)",
+            "\t401200 load_config+24 (/bin/app)",
+            "\t401000 main+16 (/bin/app)",
+        ];
+        let log = [
+            "\t7f0000001000 __libc_open64+67 (/usr/lib/libc.so.6)",
+            "\t401300 [unknown] (/bin/app)",
+        ];
+        let trace = [
+            &["Attaching 13 probes...", "1000;10;10;execve;/bin/app"][..],
+            &["1100;10;10;ustack;app"],
+            &config,
+            &["", "1200;10;10;openat;4294967196;/etc/app.conf;524288"],
+            &[
+                "1300;10;10;openat_exit;3",
+                "1400;10;10;close;3",
+                "1500;10;10;close_exit;0",
+            ],
+            &["1550;11;11;ustack;sh"],
+            &config,
+            &["1600;10;10;ustack;app"],
+            &config,
+            &["1700;10;10;openat;4294967196;/etc/app.d/extra.conf;524288"],
+            &[
+                "1800;10;10;openat_exit;3",
+                "1900;10;10;close;3",
+                "2000;10;10;close_exit;0",
+            ],
+            &["2100;10;10;ustack;app"],
+            &log,
+            &["2200;10;10;openat;4294967196;/tmp/app.log;577"],
+            &["2300;10;10;openat_exit;4", "2400;10;10;ustack;app"],
+            &log[..1],
+            &["2500;10;10;write;4;12", "2600;10;10;write_exit;12"],
+            &["2700;10;10;close;4", "2800;10;10;close_exit;0"],
+        ]
+        .concat();
+
+        let analysis = analyze(&trace);
+        let sites = analysis.call_sites();
+
+        // The stack of the other process is dropped with its syscalls
+        assert_eq!(sites.len(), 3);
+        assert_eq!(sites[0].stack.len(), 3);
+        assert_eq!(sites[0].opens, 2);
+        assert_eq!(
+            sites[0].paths,
+            BTreeSet::from(["/etc/app.conf".into(), "/etc/app.d/extra.conf".into()])
+        );
+        assert_eq!(sites[0].stack[1].to_string(), "load_config+24 (app)");
+
+        // Ties are ranked by the transferred bytes
+        assert_eq!((sites[1].writes, sites[1].bytes), (1, 12));
+        assert_eq!((sites[2].opens, sites[2].stack.len()), (1, 2));
+        assert_eq!(sites[2].stack[1].to_string(), "0x401300 (app)");
+    }
+
+    #[test]
+    fn test_transfer_call_sites() {
+        let copy = ["\t7f0000002000 copy_file_range+11 (/usr/lib/libc.so.6)"];
+        let trace = [
+            &["Attaching 13 probes...", "1000;10;10;execve;/usr/bin/cp"][..],
+            &["1100;10;10;openat;4294967196;/data/in.bin;524288"],
+            &["1200;10;10;openat_exit;3"],
+            &["1300;10;10;openat;4294967196;/data/out.bin;524865"],
+            &["1400;10;10;openat_exit;4", "1500;10;10;ustack;cp"],
+            &copy,
+            &["", "1600;10;10;transfer;3;4;1073741824"],
+            &["1700;10;10;transfer_exit;65536"],
+            &["1800;10;10;close;3", "1900;10;10;close_exit;0"],
+            &["2000;10;10;close;4", "2100;10;10;close_exit;0"],
+        ]
+        .concat();
+
+        let analysis = analyze(&trace);
+        let sites = analysis.call_sites();
+        assert_eq!(sites.len(), 1);
+        assert_eq!((sites[0].reads, sites[0].writes), (1, 1));
+        assert_eq!(sites[0].bytes, 2 * 65536);
+    }
+}
@@ -0,0 +1,145 @@
+use super::{file::SessionKind, Analysis};
+use std::collections::BTreeMap;
+
+/// The I/O of a pipe or socket label, aggregated across all threads.
+#[derive(Debug, PartialEq)]
+#[cfg_attr(test, derive(serde::Serialize))]
+pub struct ChannelSummary {
+    /// The label of the sessions, e.g. `tcp:10.0.0.1:443`.
+    pub label: String,
+    pub kind: SessionKind,
+    pub sessions: usize,
+    pub read_bytes: usize,
+    pub write_bytes: usize,
+    pub threads: usize,
+}
+
+impl Analysis {
+    /// Returns the network and IPC I/O, ordered by kind and label.
+    pub fn channel_summary(&self) -> Vec<ChannelSummary> {
+        let mut channels = BTreeMap::<(SessionKind, &str), ChannelSummary>::new();
+        for thread in self.threads() {
+            for (label, info) in thread.channels() {
+                let Some(kind) = info.sessions.first().map(|s| s.kind) else {
+                    continue;
+                };
+                let (read_bytes, write_bytes) = info.total_bytes();
+
+                let summary = channels
+                    .entry((kind, label))
+                    .or_insert_with(|| ChannelSummary {
+                        label: label.clone(),
+                        kind,
+                        sessions: 0,
+                        read_bytes: 0,
+                        write_bytes: 0,
+                        threads: 0,
+                    });
+                summary.sessions += info.sessions.len();
+                summary.read_bytes += read_bytes;
+                summary.write_bytes += write_bytes;
+                summary.threads += 1;
+            }
+        }
+
+        channels.into_values().collect()
+    }
+
+    /// Prints the I/O of pipes and sockets, separately from the files.
+    pub fn print_channels(&self) {
+        use termimad::print_inline as mdprintln;
+
+        let channels = self.channel_summary();
+        if channels.is_empty() {
+            return;
+        }
+
+        let mut table = comfy_table::Table::new();
+        table.set_header(vec![
+            "Kind", "Label", "Sessions", "Read", "Write", "Threads",
+        ]);
+        for channel in &channels {
+            table.add_row(vec![
+                channel.kind.to_string(),
+                channel.label.clone(),
+                channel.sessions.to_string(),
+                channel.read_bytes.to_string(),
+                channel.write_bytes.to_string(),
+                channel.threads.to_string(),
+            ]);
+        }
+
+        mdprintln(&format!(
+            "\n# **Network and IPC: {} channels**\n\n",
+            channels.len()
+        ));
+        println!("{table}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::{analyze, summary::SortBy};
+
+    #[test]
+    fn test_channel_summary() {
+        let analysis = analyze(&[
+            "Attaching 40 probes...",
+            "1000;10;10;execve;/usr/bin/server;bash",
+            "1000;10;10;inherited_fd;1;1;0;pipe:[42]",
+            "1100;10;10;pipe;server",
+            "1200;10;10;pipe_exit;0;3;4;server",
+            "1300;10;10;write;4;8;server",
+            "1400;10;10;write_exit;8;server",
+            "1500;10;10;read;3;64;server",
+            "1600;10;10;read_exit;8;server",
+            // A listening socket, whose connections are labeled with its address
+            "1700;10;10;socket;10;524289;server",
+            "1800;10;10;socket_exit;5;server",
+            "1900;10;10;bind;5;[::]:8080;server",
+            "2000;10;10;bind_exit;0;server",
+            "2100;10;10;accept;5;server",
+            "2200;10;10;accept_exit;6;server",
+            "2300;10;10;read;6;4096;server",
+            "2400;10;10;read_exit;120;server",
+            "2500;10;10;close;6;server",
+            "2600;10;10;close_exit;0;server",
+            // A non-blocking connect
+            "2700;10;10;socket;2;2049;server",
+            "2800;10;10;socket_exit;6;server",
+            "2900;10;10;connect;6;10.0.0.1:5432;server",
+            "3000;10;10;connect_exit;-115;server",
+            "3100;10;10;write;6;30;server",
+            "3200;10;10;write_exit;30;server",
+            "3300;10;10;write;1;5;server",
+            "3400;10;10;write_exit;5;server",
+            "3500;10;10;socketpair;1;1;server",
+            "3600;10;10;socketpair_exit;0;7;8;server",
+            "3700;10;10;openat;4294967196;/etc/hosts;524288;server",
+            "3800;10;10;openat_exit;9;server",
+        ]);
+
+        let channels = analysis
+            .channel_summary()
+            .into_iter()
+            .map(|c| (c.kind, c.label, c.sessions, c.read_bytes, c.write_bytes))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            channels,
+            [
+                (SessionKind::Pipe, "pipe".into(), 2, 8, 8),
+                (SessionKind::Pipe, "pipe:[42]".into(), 1, 0, 5),
+                (SessionKind::Unix, "unix".into(), 2, 0, 0),
+                (SessionKind::Tcp, "tcp:10.0.0.1:5432".into(), 1, 0, 30),
+                (SessionKind::Tcp, "tcp:[::]:8080".into(), 2, 120, 0),
+            ]
+        );
+
+        // Only the regular file is kept with the files, and leaked.
+        let thread = analysis.thread(10).unwrap();
+        assert!(thread.files().is_empty());
+        assert_eq!(thread.leaks()[0].path, "/etc/hosts");
+        assert!(analysis.file_summary(SortBy::Bytes).is_empty());
+    }
+}
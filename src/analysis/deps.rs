@@ -0,0 +1,173 @@
+use super::{utils, Analysis};
+use itertools::Itertools;
+use std::{collections::BTreeSet, path::Path, str::FromStr};
+
+/// The output format of the dependencies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DepsFormat {
+    /// Make-style depfile (`out.o: in.c in.h`), as written by `gcc -MD`.
+    Make,
+    Json,
+}
+
+impl DepsFormat {
+    pub const VARIANTS: &'static [&'static str] = &["make", "json"];
+}
+
+impl FromStr for DepsFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "make" => Ok(DepsFormat::Make),
+            "json" => Ok(DepsFormat::Json),
+            _ => Err(anyhow::anyhow!("Unknown deps format: {s}")),
+        }
+    }
+}
+
+/// The files a command read (inputs) and wrote (outputs).
+#[derive(Debug, Default, PartialEq, serde::Serialize)]
+pub struct Deps {
+    pub inputs: BTreeSet<String>,
+    pub outputs: BTreeSet<String>,
+}
+
+impl Deps {
+    /// Returns the Make-style depfile. The rule is written for `target`, or
+    /// for all outputs if it's not set.
+    pub fn depfile(&self, target: Option<&str>) -> String {
+        let targets = match target {
+            Some(target) => escape(target),
+            None => self.outputs.iter().map(|o| escape(o)).join(" "),
+        };
+
+        let mut output = format!("{targets}:");
+        for input in &self.inputs {
+            output.push_str(" \\\n  ");
+            output.push_str(&escape(input));
+        }
+        output.push('\n');
+        output
+    }
+
+    pub fn json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("deps are serializable") + "\n"
+    }
+
+    pub fn render(&self, format: DepsFormat, target: Option<&str>) -> String {
+        match format {
+            DepsFormat::Make => self.depfile(target),
+            DepsFormat::Json => self.json(),
+        }
+    }
+}
+
+/// Escapes the characters that have a special meaning in Makefiles.
+fn escape(path: &str) -> String {
+    path.replace('$', "$$")
+        .replace(' ', "\\ ")
+        .replace('#', "\\#")
+}
+
+impl Analysis {
+    /// Classifies the opened files into inputs and outputs. Files opened for
+    /// writing or created are outputs, even if they are read back later.
+    ///
+    /// Paths below `cwd` are relative, all others absolute.
+    pub fn deps(&self, cwd: &Path) -> Deps {
+        let mut deps = Deps::default();
+        for thread in self.threads() {
+            let sessions = thread
+                .files()
+                .values()
+                .flat_map(|info| &info.sessions)
+                .chain(thread.leaks());
+
+            for session in sessions.filter(|s| !s.is_directory() && !s.is_inherited()) {
+                // Relative paths are relative to the working directory
+                let path = utils::clean_path(&cwd.join(&session.path));
+                if utils::is_pseudo_fs(&path) {
+                    continue;
+                }
+
+                let path = path.strip_prefix(cwd).unwrap_or(&path);
+                let path = path.to_string_lossy().to_string();
+                if session.is_writable() || session.is_create() {
+                    deps.outputs.insert(path);
+                } else {
+                    deps.inputs.insert(path);
+                }
+            }
+        }
+
+        deps.inputs.retain(|input| !deps.outputs.contains(input));
+        deps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::analyze;
+
+    #[test]
+    fn test_deps_process_tree() {
+        let analysis = analyze(&[
+            "Attaching 14 probes...",
+            "1000;10;10;execve;/usr/bin/cc",
+            "1010;10;10;fork;11;cc",
+            "1020;99;99;openat;4294967196;/etc/passwd;524288;bash",
+            "1030;99;99;openat_exit;3;bash",
+            "1040;99;99;close;3;bash",
+            "1050;99;99;close_exit;0;bash",
+            // The compiler reads the sources and headers
+            "1100;11;11;execve;/usr/libexec/cc1;cc",
+            "1110;11;11;openat;4294967196;./src/../main.c;0;cc1",
+            "1120;11;11;openat_exit;3;cc1",
+            "1130;11;11;close;3;cc1",
+            "1140;11;11;close_exit;0;cc1",
+            "1150;11;11;openat;4294967196;/usr/include;589824;cc1",
+            "1160;11;11;openat_exit;4;cc1",
+            "1170;11;11;openat;4;stdio.h;0;cc1",
+            "1180;11;11;openat_exit;5;cc1",
+            "1190;11;11;close;5;cc1",
+            "1200;11;11;close_exit;0;cc1",
+            "1210;11;11;openat;4294967196;/proc/self/maps;524288;cc1",
+            "1220;11;11;openat_exit;5;cc1",
+            "1230;11;11;close;5;cc1",
+            "1240;11;11;close_exit;0;cc1",
+            "1250;11;11;openat;4294967196;/tmp/cc.s;577;cc1",
+            "1260;11;11;openat_exit;5;cc1",
+            "1270;11;11;close;5;cc1",
+            "1280;11;11;close_exit;0;cc1",
+            // The driver assembles the output of the compiler
+            "1300;10;10;openat;4294967196;/tmp/cc.s;0;cc",
+            "1310;10;10;openat_exit;3;cc",
+            "1320;10;10;close;3;cc",
+            "1330;10;10;close_exit;0;cc",
+            "1340;10;10;openat;4294967196;main.o;577;cc",
+            "1350;10;10;openat_exit;3;cc",
+            "1360;10;10;close;3;cc",
+            "1370;10;10;close_exit;0;cc",
+        ]);
+        let deps = analysis.deps(Path::new("/src/app"));
+
+        assert_eq!(
+            deps.inputs,
+            BTreeSet::from(["/usr/include/stdio.h".into(), "main.c".into()])
+        );
+        assert_eq!(
+            deps.outputs,
+            BTreeSet::from(["/tmp/cc.s".into(), "main.o".into()])
+        );
+        assert_eq!(
+            deps.depfile(None),
+            "/tmp/cc.s main.o: \\\n  /usr/include/stdio.h \\\n  main.c\n"
+        );
+        assert_eq!(
+            deps.depfile(Some("my app")),
+            deps.depfile(None).replace("/tmp/cc.s main.o", "my\\ app")
+        );
+    }
+}
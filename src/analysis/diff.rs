@@ -0,0 +1,304 @@
+use super::{summary::SortBy, utils, Analysis};
+use std::collections::BTreeMap;
+
+const COLUMNS: [&str; 7] = [
+    "Path", "Opens", "Failures", "Leaks", "Read", "Write", "Duration",
+];
+
+/// Statistics of a path that are compared between two traces.
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(test, derive(serde::Serialize))]
+pub struct FileStats {
+    /// Successful opens, including the leaked sessions.
+    pub opens: usize,
+    pub failures: usize,
+    /// Sessions that were never closed.
+    pub leaks: usize,
+    pub read_bytes: usize,
+    pub write_bytes: usize,
+    /// Cumulative time the file was open (in ns).
+    pub duration: u64,
+}
+
+/// A path that was accessed in at least one of the traces.
+#[derive(Debug, PartialEq)]
+#[cfg_attr(test, derive(serde::Serialize))]
+pub struct FileDiff {
+    pub path: String,
+    /// `None` if the path wasn't accessed before.
+    pub before: Option<FileStats>,
+    /// `None` if the path is no longer accessed.
+    pub after: Option<FileStats>,
+}
+
+impl FileDiff {
+    pub const fn is_new(&self) -> bool {
+        self.before.is_none()
+    }
+
+    pub const fn is_removed(&self) -> bool {
+        self.after.is_none()
+    }
+
+    /// Returns whether a counter changed, or the duration changed by more
+    /// than `duration_threshold` percent.
+    pub fn is_changed(&self, duration_threshold: f64) -> bool {
+        let (Some(before), Some(after)) = (&self.before, &self.after) else {
+            return false;
+        };
+
+        let counters = |s: &FileStats| (s.opens, s.failures, s.leaks, s.read_bytes, s.write_bytes);
+        counters(before) != counters(after)
+            || percent(before.duration as f64, after.duration as f64)
+                .is_none_or(|p| p.abs() > duration_threshold)
+    }
+
+    /// Returns whether the path leaks more sessions than before.
+    pub fn has_new_leaks(&self) -> bool {
+        let leaks = |s: &Option<FileStats>| s.as_ref().map(|s| s.leaks).unwrap_or_default();
+        leaks(&self.after) > leaks(&self.before)
+    }
+}
+
+/// Returns the change from `before` to `after` in percent, `None` if
+/// `before` is 0 and `after` isn't.
+fn percent(before: f64, after: f64) -> Option<f64> {
+    if before == 0.0 {
+        return (after == 0.0).then_some(0.0);
+    }
+    Some((after - before) / before * 100.0)
+}
+
+/// Formats the change of a value (e.g. `3 -> 4 (+33.3%)`).
+fn delta(before: u64, after: u64, fmt: impl Fn(u64) -> String) -> String {
+    if before == after {
+        return fmt(after);
+    }
+
+    match percent(before as f64, after as f64) {
+        Some(percent) => format!("{} -> {} ({percent:+.1}%)", fmt(before), fmt(after)),
+        None => format!("{} -> {}", fmt(before), fmt(after)),
+    }
+}
+
+/// Replaces the pid in `/proc/<pid>/...`, as it differs between runs.
+pub fn normalize_path(path: &str) -> String {
+    let Some(rest) = path.strip_prefix("/proc/") else {
+        return path.to_string();
+    };
+
+    let (pid, rest) = rest.split_once('/').unwrap_or((rest, ""));
+    if !pid.is_empty() && pid.bytes().all(|b| b.is_ascii_digit()) {
+        format!("/proc/<pid>/{rest}")
+    } else {
+        path.to_string()
+    }
+}
+
+impl Analysis {
+    /// Returns the statistics of all paths that were opened (or failed to
+    /// open), aggregated across all threads.
+    pub fn file_stats(&self) -> BTreeMap<String, FileStats> {
+        let mut files = BTreeMap::<String, FileStats>::new();
+        for summary in self.file_summary(SortBy::Bytes) {
+            let stats = files.entry(normalize_path(&summary.path)).or_default();
+            stats.opens += summary.opens;
+            stats.read_bytes += summary.read_bytes;
+            stats.write_bytes += summary.write_bytes;
+            stats.duration += summary.duration;
+        }
+
+        for thread in self.threads() {
+            for (path, errors) in thread.failed_opens() {
+                files.entry(normalize_path(path)).or_default().failures += errors.len();
+            }
+            for session in thread.leaks() {
+                let stats = files.entry(normalize_path(&session.path)).or_default();
+                stats.opens += 1;
+                stats.leaks += 1;
+            }
+        }
+
+        files
+    }
+
+    /// Compares the accessed paths with the ones of `after`, ordered by path.
+    pub fn diff(&self, after: &Analysis) -> Vec<FileDiff> {
+        let mut before = self.file_stats();
+        let mut diffs = after
+            .file_stats()
+            .into_iter()
+            .map(|(path, stats)| FileDiff {
+                before: before.remove(&path),
+                after: Some(stats),
+                path,
+            })
+            .collect::<Vec<_>>();
+        diffs.extend(before.into_iter().map(|(path, stats)| FileDiff {
+            path,
+            before: Some(stats),
+            after: None,
+        }));
+
+        diffs.sort_by(|a, b| a.path.cmp(&b.path));
+        diffs
+    }
+
+    /// Prints the new, removed and changed paths compared to `after`.
+    pub fn print_diff(&self, after: &Analysis, duration_threshold: f64) {
+        use termimad::print_inline as mdprintln;
+
+        let diffs = self.diff(after);
+        let new = diffs.iter().filter(|d| d.is_new()).collect::<Vec<_>>();
+        let removed = diffs.iter().filter(|d| d.is_removed()).collect::<Vec<_>>();
+        let changed = diffs
+            .iter()
+            .filter(|d| d.is_changed(duration_threshold))
+            .collect::<Vec<_>>();
+
+        mdprintln(&format!(
+            "\n# **Diff: {} new, {} removed, {} changed files**\n\n",
+            new.len(),
+            removed.len(),
+            changed.len()
+        ));
+
+        for (title, files) in [("New files", &new), ("Removed files", &removed)] {
+            if files.is_empty() {
+                continue;
+            }
+
+            let mut table = comfy_table::Table::new();
+            table.set_header(COLUMNS);
+            for diff in files {
+                let stats = diff.after.as_ref().or(diff.before.as_ref()).unwrap();
+                table.add_row(vec![
+                    diff.path.clone(),
+                    stats.opens.to_string(),
+                    stats.failures.to_string(),
+                    stats.leaks.to_string(),
+                    stats.read_bytes.to_string(),
+                    stats.write_bytes.to_string(),
+                    utils::format_ns(stats.duration),
+                ]);
+            }
+
+            mdprintln(&format!("\n## **{title}**\n\n"));
+            println!("{table}");
+        }
+
+        if !changed.is_empty() {
+            let mut table = comfy_table::Table::new();
+            table.set_header(COLUMNS);
+            for diff in &changed {
+                let (Some(before), Some(after)) = (&diff.before, &diff.after) else {
+                    continue;
+                };
+
+                let count = |f: fn(&FileStats) -> usize| {
+                    delta(f(before) as u64, f(after) as u64, |v| v.to_string())
+                };
+                table.add_row(vec![
+                    diff.path.clone(),
+                    count(|s| s.opens),
+                    count(|s| s.failures),
+                    count(|s| s.leaks),
+                    count(|s| s.read_bytes),
+                    count(|s| s.write_bytes),
+                    delta(before.duration, after.duration, utils::format_ns),
+                ]);
+            }
+
+            mdprintln("\n## **Changed files**\n\n");
+            println!("{table}");
+        }
+
+        let leaks = diffs
+            .iter()
+            .filter(|d| d.has_new_leaks())
+            .collect::<Vec<_>>();
+        if !leaks.is_empty() {
+            mdprintln(&format!(
+                "\n**{} files leak more sessions than before:**\n",
+                leaks.len()
+            ));
+            for diff in leaks {
+                let count = |s: &Option<FileStats>| s.as_ref().map(|s| s.leaks).unwrap_or_default();
+                mdprintln(&format!(
+                    "- `{}`: {} -> {}\n",
+                    diff.path,
+                    count(&diff.before),
+                    count(&diff.after)
+                ));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn analyze(lines: &[&str]) -> Analysis {
+        let header = ["Attaching 13 probes...", "1000;10;10;execve;/bin/app"];
+        crate::analysis::analyze(&[&header[..], lines].concat())
+    }
+
+    #[test]
+    fn test_diff() {
+        let before = analyze(&[
+            "1100;10;10;openat;4294967196;/etc/app.conf;524288",
+            "1200;10;10;openat_exit;3",
+            "1300;10;10;read;3;4096",
+            "1400;10;10;read_exit;100",
+            "1500;10;10;close;3",
+            "1600;10;10;close_exit;0",
+            "1700;10;10;openat;4294967196;/proc/10/maps;524288",
+            "1800;10;10;openat_exit;3",
+            "1900;10;10;close;3",
+            "2000;10;10;close_exit;0",
+            "2100;10;10;openat;4294967196;/etc/legacy.conf;524288",
+            "2200;10;10;openat_exit;3",
+            "2300;10;10;close;3",
+            "2400;10;10;close_exit;0",
+        ]);
+        let after = analyze(&[
+            "1100;10;10;openat;4294967196;/etc/app.conf;524288",
+            "1200;10;10;openat_exit;3",
+            "1300;10;10;read;3;4096",
+            "1400;10;10;read_exit;150",
+            "1500;10;10;close;3",
+            "1600;10;10;close_exit;0",
+            "1700;10;10;openat;4294967196;/proc/20/maps;524288",
+            "1800;10;10;openat_exit;3",
+            "1900;10;10;close;3",
+            "2000;10;10;close_exit;0",
+            "2100;10;10;openat;4294967196;/etc/app.d/extra.conf;524288",
+            "2200;10;10;openat_exit;-2",
+            "2300;10;10;openat;4294967196;/tmp/app.log;577",
+            "2400;10;10;openat_exit;3",
+        ]);
+
+        let diffs = before.diff(&after);
+        let paths = |f: fn(&FileDiff) -> bool| {
+            diffs
+                .iter()
+                .filter(|d| f(d))
+                .map(|d| d.path.as_str())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            paths(|d| d.is_new()),
+            ["/etc/app.d/extra.conf", "/tmp/app.log"]
+        );
+        assert_eq!(paths(|d| d.is_removed()), ["/etc/legacy.conf"]);
+        assert_eq!(paths(|d| d.is_changed(20.0)), ["/etc/app.conf"]);
+        assert_eq!(paths(|d| d.has_new_leaks()), ["/tmp/app.log"]);
+
+        let extra = diffs.iter().find(|d| d.path == "/etc/app.d/extra.conf");
+        assert_eq!(extra.unwrap().after.as_ref().unwrap().failures, 1);
+
+        assert_eq!(delta(100, 150, |v| v.to_string()), "100 -> 150 (+50.0%)");
+        assert_eq!(delta(0, 3, |v| v.to_string()), "0 -> 3");
+    }
+}
@@ -0,0 +1,258 @@
+use super::{
+    file::{FileEvent, FileSession, SyncOp},
+    utils, Analysis,
+};
+use itertools::Itertools;
+use std::{collections::BTreeMap, path::Path};
+
+/// The syncs and unsynced writes of a file, aggregated across all threads.
+#[derive(Debug, Default, PartialEq)]
+#[cfg_attr(test, derive(serde::Serialize))]
+pub struct Durability {
+    pub path: String,
+    /// `O_SYNC` or `O_DSYNC`, if a session was opened with it. Its writes are
+    /// synced by the kernel.
+    pub sync_flag: Option<&'static str>,
+    /// Number of `fsync` and `fdatasync` calls.
+    pub syncs: usize,
+    /// Number of `sync_file_range` calls. They only start the writeback of a
+    /// range, without the metadata or the disk cache, so the writes before
+    /// them are still unsynced.
+    pub range_syncs: usize,
+    /// Time spent in the syncs, including `sync_file_range` (in ns).
+    pub sync_time: u64,
+    /// The bytes that were written before the syncs.
+    pub synced_bytes: usize,
+    /// The most bytes that were written between two syncs.
+    pub max_synced_bytes: usize,
+    /// Number of sessions that were closed with writes after the last sync of
+    /// the file, by any session.
+    pub unsynced_closes: usize,
+    /// The bytes these sessions wrote after the last sync.
+    pub unsynced_bytes: usize,
+}
+
+impl Durability {
+    /// Returns the average bytes that were written between two syncs.
+    pub fn avg_synced_bytes(&self) -> f64 {
+        if self.syncs == 0 {
+            return 0.0;
+        }
+        self.synced_bytes as f64 / self.syncs as f64
+    }
+}
+
+impl Analysis {
+    /// Returns the durability of the written and synced files, with the most
+    /// time spent in syncs first.
+    pub fn durability(&self) -> Vec<Durability> {
+        let mut sessions = BTreeMap::<&str, Vec<&FileSession>>::new();
+        for thread in self.threads() {
+            for (path, info) in thread.files() {
+                if !utils::is_pseudo_fs(Path::new(path)) {
+                    sessions.entry(path).or_default().extend(&info.sessions);
+                }
+            }
+        }
+
+        let mut files = Vec::new();
+        for (path, sessions) in sessions {
+            let mut file = Durability {
+                path: path.to_string(),
+                sync_flag: sessions.iter().find_map(|s| s.sync_flag()),
+                ..Default::default()
+            };
+
+            // `fsync` and `fdatasync` flush the whole file, so they sync the
+            // writes of all sessions, in the order of the events.
+            let events = sessions
+                .iter()
+                .enumerate()
+                .flat_map(|(index, session)| session.events.iter().map(move |e| (index, e)))
+                .sorted_by_key(|(_, event)| event.start_ts());
+            let mut pending = vec![0; sessions.len()];
+            for (index, event) in events {
+                match event {
+                    FileEvent::Sync {
+                        op: SyncOp::SyncFileRange,
+                        ..
+                    } => {
+                        file.range_syncs += 1;
+                        file.sync_time += event.latency();
+                    }
+                    FileEvent::Sync { .. } => {
+                        let synced = pending.iter().sum();
+                        file.syncs += 1;
+                        file.sync_time += event.latency();
+                        file.synced_bytes += synced;
+                        file.max_synced_bytes = file.max_synced_bytes.max(synced);
+                        pending.fill(0);
+                    }
+                    _ if event.is_write() && !sessions[index].is_sync() => {
+                        pending[index] += event.bytes();
+                    }
+                    _ => {}
+                }
+            }
+
+            // Inherited fds aren't closed by the program (see `ThreadAnalysis::new`).
+            for (session, pending) in sessions.iter().zip(pending) {
+                if pending > 0 && !session.is_inherited() {
+                    file.unsynced_closes += 1;
+                    file.unsynced_bytes += pending;
+                }
+            }
+
+            if file.syncs > 0
+                || file.range_syncs > 0
+                || file.sync_flag.is_some()
+                || file.unsynced_closes > 0
+            {
+                files.push(file);
+            }
+        }
+
+        files.sort_by(|a, b| {
+            b.sync_time
+                .cmp(&a.sync_time)
+                .then_with(|| b.unsynced_bytes.cmp(&a.unsynced_bytes))
+                .then_with(|| a.path.cmp(&b.path))
+        });
+
+        files
+    }
+
+    /// Prints the syncs and unsynced writes of the files, if any were written.
+    pub fn print_durability(&self) {
+        use termimad::print_inline as mdprintln;
+
+        let files = self.durability();
+        if files.is_empty() {
+            return;
+        }
+
+        let mut table = comfy_table::Table::new();
+        table.set_header(vec![
+            "Path",
+            "Flag",
+            "Syncs",
+            "Range syncs",
+            "Sync time",
+            "Bytes per sync (avg / max)",
+            "Unsynced closes",
+            "Unsynced bytes",
+        ]);
+        for file in &files {
+            table.add_row(vec![
+                file.path.clone(),
+                file.sync_flag.unwrap_or_default().to_string(),
+                file.syncs.to_string(),
+                file.range_syncs.to_string(),
+                format!("{:.2} ms", utils::ns_to_ms(file.sync_time as f64)),
+                format!("{:.0} / {}", file.avg_synced_bytes(), file.max_synced_bytes),
+                file.unsynced_closes.to_string(),
+                file.unsynced_bytes.to_string(),
+            ]);
+        }
+
+        mdprintln(&format!(
+            "\n# **Durability: {} written files**\n\n",
+            files.len()
+        ));
+        println!("{table}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::analysis::analyze;
+
+    #[test]
+    fn test_durability() {
+        let analysis = analyze(&[
+            "Attaching 70 probes...",
+            "1000;10;10;execve;/usr/bin/db;bash",
+            "1000;10;10;inherited_fd;1;1;0;/dev/pts/0",
+            // A log that is synced after every second write
+            "1100;10;10;openat;4294967196;/var/lib/db/wal;1089;db",
+            "1200;10;10;openat_exit;3;db",
+            "1300;10;10;fallocate;3;0;0;1048576;db",
+            "1400;10;10;fallocate_exit;0;db",
+            "1500;10;10;write;3;100;db",
+            "1600;10;10;write_exit;100;db",
+            "1700;10;10;write;3;200;db",
+            "1800;10;10;write_exit;200;db",
+            "1900;10;10;fdatasync;3;db",
+            "5900;10;10;sync_exit;0;db",
+            "6000;10;10;write;3;50;db",
+            "6100;10;10;write_exit;50;db",
+            "6200;10;10;fsync;3;db",
+            "8200;10;10;sync_exit;0;db",
+            "8300;10;10;write;3;10;db",
+            "8400;10;10;write_exit;10;db",
+            // Only starts the writeback, the write is still unsynced
+            "8410;10;10;sync_file_range;3;0;10;db",
+            "8490;10;10;sync_exit;0;db",
+            "8500;10;10;close;3;db",
+            "8600;10;10;close_exit;0;db",
+            // Synced by the kernel
+            "8700;10;10;openat;4294967196;/var/lib/db/meta;1052737;db",
+            "8800;10;10;openat_exit;3;db",
+            "8900;10;10;write;3;64;db",
+            "9000;10;10;write_exit;64;db",
+            "9100;10;10;ftruncate;3;64;db",
+            "9200;10;10;ftruncate_exit;0;db",
+            "9300;10;10;close;3;db",
+            "9400;10;10;close_exit;0;db",
+            // Never synced
+            "9500;10;10;openat;4294967196;/var/lib/db/tmp;577;db",
+            "9600;10;10;openat_exit;3;db",
+            "9700;10;10;write;3;4096;db",
+            "9800;10;10;write_exit;4096;db",
+            "9900;10;10;close;3;db",
+            "10000;10;10;close_exit;0;db",
+            "10100;10;10;write;1;12;db",
+            "10200;10;10;write_exit;12;db",
+        ]);
+
+        insta::assert_json_snapshot!(analysis.durability());
+
+        // The syncs, truncates and allocations are events, but no I/O
+        let wal = &analysis.thread(10).unwrap().files()["/var/lib/db/wal"];
+        assert_eq!(wal.sessions[0].events.len(), 8);
+        assert_eq!(wal.total_bytes(), (0, 360));
+    }
+
+    #[test]
+    fn test_durability_across_sessions() {
+        let analysis = analyze(&[
+            "Attaching 70 probes...",
+            "1000;10;10;execve;/usr/bin/db;bash",
+            // Written and closed, then synced by another thread
+            "1100;10;10;openat;4294967196;/var/lib/db/data;1;db",
+            "1200;10;10;openat_exit;3;db",
+            "1300;10;10;write;3;100;db",
+            "1400;10;10;write_exit;100;db",
+            "1500;10;10;close;3;db",
+            "1600;10;10;close_exit;0;db",
+            "1700;10;11;openat;4294967196;/var/lib/db/data;0;syncer",
+            "1800;10;11;openat_exit;4;syncer",
+            "1900;10;11;fsync;4;syncer",
+            "2900;10;11;sync_exit;0;syncer",
+            "3000;10;11;close;4;syncer",
+            "3100;10;11;close_exit;0;syncer",
+            // Written after the last sync
+            "3200;10;10;openat;4294967196;/var/lib/db/data;1;db",
+            "3300;10;10;openat_exit;3;db",
+            "3400;10;10;write;3;50;db",
+            "3500;10;10;write_exit;50;db",
+            "3600;10;10;close;3;db",
+            "3700;10;10;close_exit;0;db",
+        ]);
+
+        let files = analysis.durability();
+        assert_eq!(files.len(), 1);
+        assert_eq!((files[0].syncs, files[0].synced_bytes), (1, 100));
+        assert_eq!((files[0].unsynced_closes, files[0].unsynced_bytes), (1, 50));
+    }
+}
@@ -1,4 +1,10 @@
 use super::utils;
+use crate::{
+    stack::Frame,
+    syscall::{fd_t, mmap_flags::*, open_flags::*, socket::*},
+};
+use itertools::Itertools;
+use std::fmt;
 
 #[derive(Debug, Default)]
 #[cfg_attr(test, derive(serde::Serialize))]
@@ -8,79 +14,38 @@ pub struct FileInfo {
 
 // Aggregation
 impl FileInfo {
-    /// Returns the total number of read and write events.
+    /// Returns the total number of read and written bytes.
     pub fn total_bytes(&self) -> (usize, usize) {
-        let mut total_read_bytes = 0;
-        let mut total_write_bytes = 0;
-
-        for session in &self.sessions {
-            for event in &session.events {
-                match event {
-                    FileEvent::Read { bytes, .. } => {
-                        total_read_bytes += *bytes;
-                    }
-                    FileEvent::Write { bytes, .. } => {
-                        total_write_bytes += *bytes;
-                    }
-                }
-            }
-        }
+        (
+            self.read_stats().returned.sum,
+            self.write_stats().returned.sum,
+        )
+    }
 
-        (total_read_bytes, total_write_bytes)
-    }
-
-    /// Returns average read and write size.
-    pub fn avg_size(&self) -> Option<(f64, f64)> {
-        let mut total_read_bytes = 0_f64;
-        let mut total_read_count = 0_usize;
-
-        let mut total_write_bytes = 0_f64;
-        let mut total_write_count = 0_usize;
-
-        for session in &self.sessions {
-            for event in &session.events {
-                match event {
-                    FileEvent::Read { bytes, .. } => {
-                        total_read_bytes += *bytes as f64;
-                        total_read_count += 1;
-                    }
-                    FileEvent::Write { bytes, .. } => {
-                        total_write_bytes += *bytes as f64;
-                        total_write_count += 1;
-                    }
-                }
-            }
-        }
+    /// Returns the number of mapped bytes of all sessions (see
+    /// [`FileSession::mapped_bytes`]).
+    pub fn mapped_bytes(&self) -> u64 {
+        self.sessions.iter().map(|s| s.mapped_bytes()).sum()
+    }
+}
 
-        if total_read_count == 0 || total_write_count == 0 {
-            return None;
-        }
+// Temporal
+impl FileInfo {
+    /// Returns the time all sessions were open for, in nanoseconds.
+    pub fn total_duration(&self) -> u64 {
+        self.sessions.iter().map(|s| s.duration()).sum()
+    }
 
-        Some((
-            total_read_bytes / total_read_count as f64,
-            total_write_bytes / total_write_count as f64,
-        ))
-    }
-
-    /// Returns the maximum read and write size.
-    pub fn max_size(&self) -> (usize, usize) {
-        let mut max_read = 0;
-        let mut max_write = 0;
-
-        for session in &self.sessions {
-            for event in &session.events {
-                match event {
-                    FileEvent::Read { bytes, .. } => {
-                        max_read = max_read.max(*bytes);
-                    }
-                    FileEvent::Write { bytes, .. } => {
-                        max_write = max_write.max(*bytes);
-                    }
-                }
-            }
-        }
+    pub fn total_duration_ms(&self) -> f64 {
+        utils::ns_to_ms(self.total_duration() as f64)
+    }
 
-        (max_read, max_write)
+    pub fn avg_duration_ms(&self) -> f64 {
+        self.total_duration_ms() / self.sessions.len() as f64
+    }
+
+    pub fn total_idle_time_ms(&self) -> f64 {
+        self.sessions.iter().map(|s| s.idle_time_ms()).sum()
     }
 }
 
@@ -89,8 +54,37 @@ impl FileInfo {
 pub struct FileSession {
     pub events: Vec<FileEvent>,
     pub path: String,
+    /// Flags of the `open` syscall.
+    pub flags: u64,
     pub open_ts: u64,
+    /// Timestamp of the `open` syscall exit, 0 if unknown.
+    pub open_end_ts: u64,
     pub close_ts: u64,
+    /// Timestamp of the `close` syscall exit, 0 if unknown.
+    pub close_end_ts: u64,
+    /// User-space stack of the `open` syscall, if it was recorded.
+    #[cfg_attr(test, serde(skip_serializing_if = "Option::is_none"))]
+    pub stack: Option<Vec<Frame>>,
+    /// The inherited fd (e.g. 1 for stdout) the session was duplicated from,
+    /// `None` if the file was opened. Inherited sessions start with the
+    /// thread.
+    #[cfg_attr(test, serde(skip_serializing_if = "Option::is_none"))]
+    pub inherited: Option<fd_t>,
+    /// What the fd refers to. The path of pipes and sockets is a label like
+    /// `tcp:10.0.0.1:443` (see [`SessionKind::label`]).
+    #[cfg_attr(test, serde(skip_serializing_if = "SessionKind::is_file"))]
+    pub kind: SessionKind,
+    /// The mappings of the fd, which may outlive the session.
+    #[cfg_attr(test, serde(skip_serializing_if = "Vec::is_empty"))]
+    pub mappings: Vec<Mapping>,
+    /// Whether a read returned 0 bytes, i.e. reached the end of the file. EOF
+    /// reads aren't recorded as events.
+    #[cfg_attr(test, serde(skip_serializing_if = "std::ops::Not::not"))]
+    pub eof: bool,
+    /// Whether the fd was listed (`getdents64`) or used as the dirfd of a
+    /// metadata syscall. These are recorded per path, not as events.
+    #[cfg_attr(test, serde(skip_serializing_if = "std::ops::Not::not"))]
+    pub used_as_dir: bool,
 }
 
 impl FileSession {
@@ -102,6 +96,181 @@ impl FileSession {
     }
 }
 
+/// What the fd of a session refers to.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(test, derive(serde::Serialize))]
+pub enum SessionKind {
+    #[default]
+    File,
+    Pipe,
+    Unix,
+    Tcp,
+    Udp,
+    /// A socket of another family (e.g. netlink), or of an unknown one.
+    Socket,
+}
+
+impl SessionKind {
+    /// Returns the kind of a socket created with `socket(domain, type)`.
+    pub const fn from_socket(domain: u64, socket_type: u64) -> Self {
+        match (domain, socket_type & SOCK_TYPE_MASK) {
+            (AF_UNIX, _) => SessionKind::Unix,
+            (AF_INET | AF_INET6, SOCK_STREAM) => SessionKind::Tcp,
+            (AF_INET | AF_INET6, SOCK_DGRAM) => SessionKind::Udp,
+            _ => SessionKind::Socket,
+        }
+    }
+
+    /// Returns the kind of an fd from its `/proc/<pid>/fd` target (e.g.
+    /// `pipe:[1234]`).
+    pub fn from_path(path: &str) -> Self {
+        if path.starts_with("pipe:[") {
+            SessionKind::Pipe
+        } else if path.starts_with("socket:[") {
+            SessionKind::Socket
+        } else {
+            SessionKind::File
+        }
+    }
+
+    pub const fn is_file(&self) -> bool {
+        matches!(self, SessionKind::File)
+    }
+
+    /// Returns the path of a session of this kind, e.g. `unix:/run/app.sock`,
+    /// or only the kind if the address is unknown.
+    pub fn label(&self, address: &str) -> String {
+        if address.is_empty() {
+            self.to_string()
+        } else {
+            format!("{self}:{address}")
+        }
+    }
+}
+
+impl fmt::Display for SessionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SessionKind::File => "file",
+            SessionKind::Pipe => "pipe",
+            SessionKind::Unix => "unix",
+            SessionKind::Tcp => "tcp",
+            SessionKind::Udp => "udp",
+            SessionKind::Socket => "socket",
+        })
+    }
+}
+
+// Flags
+impl FileSession {
+    /// Returns whether the fd was inherited (e.g. stdout) instead of opened.
+    pub const fn is_inherited(&self) -> bool {
+        self.inherited.is_some()
+    }
+
+    /// Returns whether the file was opened for writing (`O_WRONLY` or
+    /// `O_RDWR`).
+    pub const fn is_writable(&self) -> bool {
+        matches!(self.flags & O_ACCMODE, O_WRONLY | O_RDWR)
+    }
+
+    /// Returns whether the file was opened for reading (`O_RDONLY` or
+    /// `O_RDWR`).
+    pub const fn is_readable(&self) -> bool {
+        self.flags & O_ACCMODE != O_WRONLY
+    }
+
+    /// Returns whether the file is created if it doesn't exist (`O_CREAT`).
+    pub const fn is_create(&self) -> bool {
+        self.flags & O_CREAT != 0
+    }
+
+    pub const fn is_directory(&self) -> bool {
+        self.flags & O_DIRECTORY != 0
+    }
+
+    /// Returns whether writes are synced by the kernel (`O_SYNC` or
+    /// `O_DSYNC`).
+    pub const fn is_sync(&self) -> bool {
+        self.flags & O_DSYNC != 0
+    }
+
+    /// Returns the flag the file was opened with for synced writes, if any.
+    pub const fn sync_flag(&self) -> Option<&'static str> {
+        if self.flags & O_SYNC == O_SYNC {
+            Some("O_SYNC")
+        } else if self.is_sync() {
+            Some("O_DSYNC")
+        } else {
+            None
+        }
+    }
+}
+
+// Mappings
+impl FileSession {
+    /// Returns the number of bytes of the file that were mapped. Ranges that
+    /// were mapped more than once (e.g. by the loader) are only counted once.
+    pub fn mapped_bytes(&self) -> u64 {
+        let (mut total, mut end) = (0, 0);
+        for (start, stop) in self
+            .mappings
+            .iter()
+            .map(|m| (m.offset, m.offset + m.len))
+            .sorted()
+        {
+            if stop > end {
+                total += stop - start.max(end);
+                end = stop;
+            }
+        }
+        total
+    }
+}
+
+/// A file-backed `mmap` of a session.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(test, derive(serde::Serialize))]
+pub struct Mapping {
+    pub addr: u64,
+    pub len: u64,
+    pub prot: u64,
+    pub flags: u64,
+    /// The offset in the file.
+    pub offset: u64,
+    pub map_ts: u64,
+    /// Timestamp of the first `munmap` or fixed `mmap` that overlaps the
+    /// range, 0 if it was still mapped at the end of the trace. A partial
+    /// overlap ends the whole mapping.
+    pub unmap_ts: u64,
+    /// Number of `msync` calls on the mapping.
+    pub syncs: usize,
+}
+
+impl Mapping {
+    /// Returns whether the mapping overlaps the memory range.
+    pub const fn overlaps(&self, addr: u64, len: u64) -> bool {
+        addr < self.addr + self.len && self.addr < addr + len
+    }
+
+    /// Returns the protection like `ls` does (e.g. `r-x`), with `shared` or
+    /// `private`.
+    pub fn format(&self) -> String {
+        let bit = |flag, c| if self.prot & flag != 0 { c } else { '-' };
+        let sharing = if self.flags & MAP_SHARED != 0 {
+            "shared"
+        } else {
+            "private"
+        };
+        format!(
+            "{}{}{} {sharing}",
+            bit(PROT_READ, 'r'),
+            bit(PROT_WRITE, 'w'),
+            bit(PROT_EXEC, 'x')
+        )
+    }
+}
+
 // Temporal
 impl FileSession {
     pub const fn duration(&self) -> u64 {
@@ -112,6 +281,20 @@ impl FileSession {
         utils::ns_to_ms(self.duration() as f64)
     }
 
+    /// Returns how long the `open` syscall took, if the exit was traced.
+    pub fn open_latency(&self) -> Option<u64> {
+        self.open_end_ts
+            .checked_sub(self.open_ts)
+            .filter(|_| self.open_end_ts != 0)
+    }
+
+    /// Returns how long the `close` syscall took, if the exit was traced.
+    pub fn close_latency(&self) -> Option<u64> {
+        self.close_end_ts
+            .checked_sub(self.close_ts)
+            .filter(|_| self.close_end_ts != 0)
+    }
+
     pub fn idle_time_ms(&self) -> f64 {
         let mut total_idle = 0;
         let mut last_end = self.open_ts;
@@ -121,6 +304,7 @@ impl FileSession {
             total_idle += event_start - last_end;
             last_end = event.end_ts();
         }
+        total_idle += self.close_ts.saturating_sub(last_end);
 
         utils::ns_to_ms(total_idle as f64)
     }
@@ -130,22 +314,85 @@ impl FileSession {
 #[cfg_attr(test, derive(serde::Serialize))]
 pub enum FileEvent {
     Read {
+        /// The number of bytes that were requested.
+        requested: usize,
         bytes: usize,
         start_ts: u64,
         end_ts: u64,
+        /// User-space stack of the syscall, if it was recorded.
+        #[cfg_attr(test, serde(skip_serializing_if = "Option::is_none"))]
+        stack: Option<Vec<Frame>>,
     },
     Write {
+        requested: usize,
         bytes: usize,
         start_ts: u64,
         end_ts: u64,
+        #[cfg_attr(test, serde(skip_serializing_if = "Option::is_none"))]
+        stack: Option<Vec<Frame>>,
+    },
+    /// A copy between two fds in the kernel (e.g. `sendfile`), which is
+    /// recorded on the sessions of both ends.
+    Transfer {
+        requested: usize,
+        bytes: usize,
+        start_ts: u64,
+        end_ts: u64,
+        #[cfg_attr(test, serde(skip_serializing_if = "Option::is_none"))]
+        stack: Option<Vec<Frame>>,
+        /// Whether the bytes were copied from this session, instead of to it.
+        is_source: bool,
+        /// The path of the session at the other end, empty if it's unknown.
+        peer: String,
+    },
+    /// `fsync`, `fdatasync` or `sync_file_range`.
+    Sync {
+        op: SyncOp,
+        start_ts: u64,
+        end_ts: u64,
+    },
+    /// `ftruncate` to the length.
+    Truncate {
+        length: u64,
+        start_ts: u64,
+        end_ts: u64,
+    },
+    /// `fallocate` of the range.
+    Allocate {
+        offset: u64,
+        len: u64,
+        start_ts: u64,
+        end_ts: u64,
     },
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(test, derive(serde::Serialize))]
+pub enum SyncOp {
+    Fsync,
+    Fdatasync,
+    SyncFileRange,
+}
+
+impl SyncOp {
+    pub const fn name(self) -> &'static str {
+        match self {
+            SyncOp::Fsync => "fsync",
+            SyncOp::Fdatasync => "fdatasync",
+            SyncOp::SyncFileRange => "sync_file_range",
+        }
+    }
+}
+
 impl FileEvent {
     pub const fn start_ts(&self) -> u64 {
         match self {
             FileEvent::Read { start_ts, .. } => *start_ts,
             FileEvent::Write { start_ts, .. } => *start_ts,
+            FileEvent::Transfer { start_ts, .. } => *start_ts,
+            FileEvent::Sync { start_ts, .. } => *start_ts,
+            FileEvent::Truncate { start_ts, .. } => *start_ts,
+            FileEvent::Allocate { start_ts, .. } => *start_ts,
         }
     }
 
@@ -153,6 +400,58 @@ impl FileEvent {
         match self {
             FileEvent::Read { end_ts, .. } => *end_ts,
             FileEvent::Write { end_ts, .. } => *end_ts,
+            FileEvent::Transfer { end_ts, .. } => *end_ts,
+            FileEvent::Sync { end_ts, .. } => *end_ts,
+            FileEvent::Truncate { end_ts, .. } => *end_ts,
+            FileEvent::Allocate { end_ts, .. } => *end_ts,
+        }
+    }
+
+    /// Returns the (requested, returned) sizes of reads, writes and transfers.
+    pub const fn sizes(&self) -> Option<(usize, usize)> {
+        match self {
+            FileEvent::Read {
+                requested, bytes, ..
+            }
+            | FileEvent::Write {
+                requested, bytes, ..
+            }
+            | FileEvent::Transfer {
+                requested, bytes, ..
+            } => Some((*requested, *bytes)),
+            _ => None,
         }
     }
+
+    /// Returns the bytes that were read or written, 0 for other events.
+    pub const fn bytes(&self) -> usize {
+        match self.sizes() {
+            Some((_, bytes)) => bytes,
+            None => 0,
+        }
+    }
+
+    /// Returns whether the bytes were read from the file, which includes the
+    /// transfers it was the source of.
+    pub const fn is_read(&self) -> bool {
+        match self {
+            FileEvent::Read { .. } => true,
+            FileEvent::Transfer { is_source, .. } => *is_source,
+            _ => false,
+        }
+    }
+
+    /// Returns whether the bytes were written to the file, which includes the
+    /// transfers it was the destination of.
+    pub const fn is_write(&self) -> bool {
+        match self {
+            FileEvent::Write { .. } => true,
+            FileEvent::Transfer { is_source, .. } => !*is_source,
+            _ => false,
+        }
+    }
+
+    pub const fn latency(&self) -> u64 {
+        self.end_ts() - self.start_ts()
+    }
 }
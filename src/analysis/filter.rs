@@ -0,0 +1,159 @@
+use regex::Regex;
+
+/// Patterns that match the files the dynamic linker opens while loading a
+/// program (`ld.so.cache`, shared libraries, ...).
+pub const LOADER_PATTERNS: &[&str] = &["/etc/ld.so.*", "*.so", "*.so.*", "*/glibc-hwcaps/*"];
+
+/// A single path pattern. Patterns prefixed with `regex:` are regular
+/// expressions, all others are globs (e.g. `/usr/lib/**`).
+#[derive(Debug, Clone)]
+pub enum PathPattern {
+    Glob(glob::Pattern),
+    Regex(Regex),
+}
+
+impl PathPattern {
+    pub fn parse(pattern: &str) -> anyhow::Result<Self> {
+        match pattern.strip_prefix("regex:") {
+            Some(regex) => Ok(Self::Regex(Regex::new(regex)?)),
+            None => Ok(Self::Glob(glob::Pattern::new(pattern)?)),
+        }
+    }
+
+    pub fn matches(&self, path: &str) -> bool {
+        match self {
+            PathPattern::Glob(glob) => glob.matches(path),
+            PathPattern::Regex(regex) => regex.is_match(path),
+        }
+    }
+
+    /// Returns the prefix if the pattern is a literal path followed by a
+    /// trailing wildcard (e.g. `/usr/lib/*` or `/usr/lib/**`).
+    ///
+    /// Only these patterns can be evaluated inside of the bpftrace script.
+    pub fn as_prefix(&self) -> Option<&str> {
+        let PathPattern::Glob(glob) = self else {
+            return None;
+        };
+
+        let prefix = glob.as_str().trim_end_matches('*');
+        if prefix.is_empty()
+            || prefix.len() == glob.as_str().len()
+            || prefix.contains(['*', '?', '[', ']'])
+        {
+            return None;
+        }
+
+        Some(prefix)
+    }
+}
+
+/// Include and exclude rules for file paths.
+///
+/// A path is kept if it matches at least one include pattern (or there are
+/// none), and doesn't match any exclude pattern.
+#[derive(Debug, Clone, Default)]
+pub struct PathFilter {
+    include: Vec<PathPattern>,
+    exclude: Vec<PathPattern>,
+}
+
+impl PathFilter {
+    pub fn new(include: &[String], exclude: &[String]) -> anyhow::Result<Self> {
+        Ok(Self {
+            include: include
+                .iter()
+                .map(|p| PathPattern::parse(p))
+                .collect::<anyhow::Result<_>>()?,
+            exclude: exclude
+                .iter()
+                .map(|p| PathPattern::parse(p))
+                .collect::<anyhow::Result<_>>()?,
+        })
+    }
+
+    /// Adds the [`LOADER_PATTERNS`] to the exclude rules.
+    pub fn exclude_loader(mut self) -> Self {
+        self.exclude.extend(
+            LOADER_PATTERNS
+                .iter()
+                .map(|p| PathPattern::parse(p).expect("loader patterns are valid")),
+        );
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    pub fn matches(&self, path: &str) -> bool {
+        let included = self.include.is_empty() || self.include.iter().any(|p| p.matches(path));
+        included && !self.exclude.iter().any(|p| p.matches(path))
+    }
+
+    /// Returns the include prefixes, but only if *all* include patterns can
+    /// be expressed as a prefix. Otherwise we would drop paths that one of the
+    /// other patterns would have kept.
+    pub fn include_prefixes(&self) -> Vec<&str> {
+        self.include
+            .iter()
+            .map(|p| p.as_prefix())
+            .collect::<Option<Vec<_>>>()
+            .unwrap_or_default()
+    }
+
+    /// Returns the prefixes of all exclude patterns that can be expressed as
+    /// one. The remaining patterns are still applied during the analysis.
+    pub fn exclude_prefixes(&self) -> Vec<&str> {
+        self.exclude.iter().filter_map(|p| p.as_prefix()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_matches() {
+        let filter = PathFilter::new(
+            &["/etc/*".into(), "regex:^/proc/self/(maps|status)$".into()],
+            &["/etc/ld.so.*".into()],
+        )
+        .unwrap();
+
+        assert!(filter.matches("/etc/passwd"));
+        assert!(filter.matches("/proc/self/maps"));
+        assert!(!filter.matches("/etc/ld.so.cache"));
+        assert!(!filter.matches("/usr/lib/libc.so.6"));
+        assert!(!filter.matches("/proc/self/mapsx"));
+    }
+
+    #[test]
+    fn test_filter_loader() {
+        let filter = PathFilter::default().exclude_loader();
+
+        assert!(!filter.matches("/etc/ld.so.cache"));
+        assert!(!filter.matches("/lib/x86_64-linux-gnu/libc.so.6"));
+        assert!(!filter.matches("/lib/x86_64-linux-gnu/libgcc_s.so.1"));
+        assert!(!filter.matches("/usr/lib/glibc-hwcaps/x86-64-v3/libm.so.6"));
+        assert!(filter.matches("/etc/passwd"));
+        assert!(filter.matches("/proc/self/maps"));
+    }
+
+    #[test]
+    fn test_filter_prefixes() {
+        let filter = PathFilter::new(
+            &["/etc/**".into(), "/home/*".into()],
+            &["/etc/ld.so.*".into(), "*.so".into(), "regex:^/tmp".into()],
+        )
+        .unwrap();
+        assert_eq!(filter.include_prefixes(), vec!["/etc/", "/home/"]);
+        assert_eq!(filter.exclude_prefixes(), vec!["/etc/ld.so."]);
+
+        let filter = PathFilter::new(&["/etc/**".into(), "regex:^/home".into()], &[]).unwrap();
+        assert!(filter.include_prefixes().is_empty());
+
+        let filter = PathFilter::new(&[], &["/etc/ld.so.cache".into()]).unwrap();
+        assert!(filter.exclude_prefixes().is_empty());
+    }
+}
@@ -0,0 +1,207 @@
+use super::{utils, Analysis};
+use crate::syscall::tid_t;
+
+/// Thresholds of the idle session analysis.
+#[derive(Debug, Clone, Copy)]
+pub struct IdleConfig {
+    /// Sessions that are idle for more than this fraction of their duration
+    /// are reported.
+    pub ratio: f64,
+    /// Minimum duration (in ns) of an idle session to report it.
+    pub min_duration: u64,
+}
+
+impl Default for IdleConfig {
+    fn default() -> Self {
+        Self {
+            ratio: 0.9,
+            min_duration: 100_000_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(test, derive(serde::Serialize))]
+pub enum IdleKind {
+    /// The file was opened and closed without any reads, writes, mappings or
+    /// directory listings.
+    Unused,
+    /// The file was held open much longer than it was used.
+    Idle,
+}
+
+#[derive(Debug, PartialEq)]
+#[cfg_attr(test, derive(serde::Serialize))]
+pub struct IdleSession {
+    pub kind: IdleKind,
+    pub tid: tid_t,
+    pub path: String,
+    /// Index of the session in [`FileInfo::sessions`](super::file::FileInfo).
+    pub session: usize,
+    pub open_ts: u64,
+    /// Duration of the session (in ns).
+    pub duration: u64,
+    pub idle_ratio: f64,
+}
+
+impl Analysis {
+    /// Returns the sessions that didn't read or write, and the sessions that
+    /// held the file open while being idle, ordered by open timestamp.
+    pub fn idle_sessions(&self, config: &IdleConfig) -> Vec<IdleSession> {
+        let mut findings = Vec::new();
+        for thread in self.threads() {
+            for (path, info) in thread.files() {
+                for (index, session) in info.sessions.iter().enumerate() {
+                    // A mapped file is used through the memory, and a directory
+                    // through metadata syscalls, neither recorded as events, so
+                    // there's no telling when they're idle.
+                    if !session.mappings.is_empty() || session.used_as_dir {
+                        continue;
+                    }
+
+                    let idle_ratio = if session.events.is_empty() {
+                        1.0
+                    } else if session.duration() == 0 {
+                        0.0
+                    } else {
+                        session.idle_time_ms() / session.duration_ms()
+                    };
+
+                    let kind = if session.events.is_empty() {
+                        IdleKind::Unused
+                    } else if idle_ratio > config.ratio && session.duration() >= config.min_duration
+                    {
+                        IdleKind::Idle
+                    } else {
+                        continue;
+                    };
+
+                    findings.push(IdleSession {
+                        kind,
+                        tid: thread.tid(),
+                        path: path.clone(),
+                        session: index,
+                        open_ts: session.open_ts,
+                        duration: session.duration(),
+                        idle_ratio,
+                    });
+                }
+            }
+        }
+
+        findings.sort_by_key(|f| (f.open_ts, f.tid));
+        findings
+    }
+
+    pub fn print_idle_sessions(&self, config: &IdleConfig) {
+        use termimad::print_inline as mdprintln;
+
+        let findings = self.idle_sessions(config);
+        if findings.is_empty() {
+            return;
+        }
+
+        let mut table = comfy_table::Table::new();
+        table.set_header(vec![
+            "Thread",
+            "Path",
+            "Session",
+            "Opened at",
+            "Duration",
+            "Idle",
+            "Finding",
+        ]);
+        for finding in &findings {
+            let kind = match finding.kind {
+                IdleKind::Unused => "opened without use",
+                IdleKind::Idle => "held open while idle",
+            };
+            table.add_row(vec![
+                self.thread_label(finding.tid),
+                finding.path.clone(),
+                (finding.session + 1).to_string(),
+                format!(
+                    "+{:.2} ms",
+                    utils::ns_to_ms(finding.open_ts.saturating_sub(self.start_ts()) as f64)
+                ),
+                utils::format_ns(finding.duration),
+                format!("{:.1}%", finding.idle_ratio * 100.0),
+                kind.to_string(),
+            ]);
+        }
+
+        mdprintln(&format!(
+            "\n# **Unused and idle sessions: {}**\n\n",
+            findings.len()
+        ));
+        println!("{table}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{analysis::analyze, tracer::BpfTracer};
+
+    #[test]
+    fn test_idle_threaded() {
+        let raw_trace = include_str!("../../data/threaded.txt");
+        let analysis = Analysis::new(BpfTracer::parse_trace(raw_trace).unwrap());
+        let findings = analysis.idle_sessions(&IdleConfig::default());
+
+        // The `/etc/passwd` session that is held for 2s without a read, and the one
+        // that waits 3s before reading.
+        let passwd = findings
+            .iter()
+            .filter(|f| f.path == "/etc/passwd")
+            .map(|f| (f.kind, f.session))
+            .collect::<Vec<_>>();
+        assert_eq!(passwd, vec![(IdleKind::Unused, 0), (IdleKind::Idle, 1)]);
+
+        // Every second `/etc/hosts` session doesn't read, the others wait 500ms
+        // before reading.
+        let hosts = findings
+            .iter()
+            .filter(|f| f.path == "/etc/hosts")
+            .map(|f| f.kind)
+            .collect::<Vec<_>>();
+        assert_eq!(hosts, [IdleKind::Idle, IdleKind::Unused].repeat(5));
+    }
+
+    #[test]
+    fn test_idle_after_use() {
+        // Read right after the open, then held for 1s before the close
+        let analysis = analyze(&[
+            "Attaching 13 probes...",
+            "1000;10;10;execve;/bin/app",
+            "1100;10;10;openat;4294967196;/etc/app.conf;524288",
+            "1200;10;10;openat_exit;3",
+            "1300;10;10;read;3;4096",
+            "1400;10;10;read_exit;100",
+            "1000001400;10;10;close;3",
+            "1000001500;10;10;close_exit;0",
+        ]);
+        let findings = analysis.idle_sessions(&IdleConfig::default());
+
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].kind, IdleKind::Idle);
+        assert!(findings[0].idle_ratio > 0.99);
+    }
+
+    #[test]
+    fn test_idle_mapped() {
+        // Mapped and held open for 1s without any syscalls
+        let analysis = analyze(&[
+            "Attaching 13 probes...",
+            "1000;10;10;execve;/bin/app",
+            "1100;10;10;openat;4294967196;/usr/lib/libc.so.6;524288",
+            "1200;10;10;openat_exit;3",
+            "1300;10;10;mmap;3;2000000;1;2050;0",
+            "1400;10;10;mmap_exit;140000000000000",
+            "1000001500;10;10;close;3",
+            "1000001600;10;10;close_exit;0",
+        ]);
+
+        assert_eq!(analysis.idle_sessions(&IdleConfig::default()), []);
+    }
+}
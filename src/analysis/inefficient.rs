@@ -0,0 +1,203 @@
+use super::{file::FileEvent, latency::Op, Analysis};
+use crate::syscall::tid_t;
+
+/// Thresholds of the inefficient I/O analysis.
+#[derive(Debug, Clone, Copy)]
+pub struct SmallIoConfig {
+    /// Reads and writes below this size (in bytes) are considered small.
+    pub threshold: usize,
+    /// Minimum number of small syscalls in a session to report it.
+    pub min_calls: usize,
+    /// Size of the buffer (in bytes) used to estimate the saved syscalls.
+    pub buffer_size: usize,
+}
+
+impl Default for SmallIoConfig {
+    fn default() -> Self {
+        Self {
+            threshold: 512,
+            min_calls: 16,
+            buffer_size: 8 * 1024,
+        }
+    }
+}
+
+/// A session that issued many small reads or writes.
+#[derive(Debug, PartialEq)]
+#[cfg_attr(test, derive(serde::Serialize))]
+pub struct SmallIo {
+    pub tid: tid_t,
+    pub path: String,
+    /// Index of the session in [`FileInfo::sessions`](super::file::FileInfo).
+    pub session: usize,
+    pub op: Op,
+    /// Number of small syscalls.
+    pub calls: usize,
+    /// Bytes transferred by the small syscalls.
+    pub bytes: usize,
+}
+
+impl SmallIo {
+    pub fn bytes_per_call(&self) -> f64 {
+        self.bytes as f64 / self.calls as f64
+    }
+
+    /// Returns how many syscalls are saved if the bytes were transferred with
+    /// a buffer of `buffer_size` bytes.
+    pub fn saved_calls(&self, buffer_size: usize) -> usize {
+        self.calls
+            .saturating_sub(self.bytes.div_ceil(buffer_size.max(1)))
+    }
+}
+
+impl Analysis {
+    /// Returns the sessions with many small reads or writes, worst offenders
+    /// (most saved syscalls) first.
+    pub fn small_io(&self, config: &SmallIoConfig) -> Vec<SmallIo> {
+        let mut findings = Vec::new();
+        for thread in self.threads() {
+            for (path, info) in thread.files() {
+                for (index, session) in info.sessions.iter().enumerate() {
+                    for op in [Op::Read, Op::Write] {
+                        let sizes = session
+                            .events
+                            .iter()
+                            .filter_map(|event| match (event, op) {
+                                (FileEvent::Read { bytes, .. }, Op::Read)
+                                | (FileEvent::Write { bytes, .. }, Op::Write) => Some(*bytes),
+                                _ => None,
+                            })
+                            .filter(|bytes| *bytes < config.threshold);
+
+                        let (calls, bytes) = sizes.fold((0, 0), |(c, b), size| (c + 1, b + size));
+                        if calls >= config.min_calls.max(1) {
+                            findings.push(SmallIo {
+                                tid: thread.tid(),
+                                path: path.clone(),
+                                session: index,
+                                op,
+                                calls,
+                                bytes,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+
+        findings.sort_by(|a, b| {
+            b.saved_calls(config.buffer_size)
+                .cmp(&a.saved_calls(config.buffer_size))
+                .then_with(|| (a.tid, &a.path, a.session).cmp(&(b.tid, &b.path, b.session)))
+        });
+        findings
+    }
+
+    /// Prints the `top` sessions with the most small reads or writes.
+    pub fn print_small_io(&self, config: &SmallIoConfig, top: usize) {
+        use termimad::print_inline as mdprintln;
+
+        let findings = self.small_io(config);
+        if findings.is_empty() {
+            return;
+        }
+
+        let mut table = comfy_table::Table::new();
+        table.set_header(vec![
+            "Thread",
+            "Path",
+            "Session",
+            "Op",
+            "Syscalls",
+            "Bytes/call",
+            format!("Saved with {} KiB buffer", config.buffer_size / 1024).as_str(),
+        ]);
+        for finding in findings.iter().take(top) {
+            table.add_row(vec![
+                self.thread_label(finding.tid),
+                finding.path.clone(),
+                (finding.session + 1).to_string(),
+                finding.op.name().to_string(),
+                finding.calls.to_string(),
+                format!("{:.2}", finding.bytes_per_call()),
+                finding.saved_calls(config.buffer_size).to_string(),
+            ]);
+        }
+
+        mdprintln(&format!(
+            "\n# **Inefficient I/O: {} sessions with reads or writes below {} bytes**\n\n",
+            findings.len(),
+            config.threshold
+        ));
+        println!("{table}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::analyze;
+
+    #[test]
+    fn test_small_writes() {
+        let mut trace = [
+            "Attaching 11 probes...",
+            "1000;10;10;execve;/bin/app",
+            "1100;10;10;openat;4294967196;/tmp/app.log;577",
+            "1200;10;10;openat_exit;3",
+            "1300;10;10;openat;4294967196;/etc/hosts;524288",
+            "1400;10;10;openat_exit;4",
+            "1500;10;10;read;4;4096",
+            "1600;10;10;read_exit;326",
+        ]
+        .map(String::from)
+        .to_vec();
+        for i in 0..100 {
+            let ts = 2000 + i * 100;
+            trace.push(format!("{ts};10;10;write;3;1"));
+            trace.push(format!("{};10;10;write_exit;1", ts + 10));
+        }
+        trace.push("20000;10;10;close;3".into());
+        trace.push("20010;10;10;close_exit;0".into());
+        trace.push("20100;10;10;close;4".into());
+        trace.push("20110;10;10;close_exit;0".into());
+
+        let analysis = analyze(&trace);
+        let findings = analysis.small_io(&SmallIoConfig::default());
+
+        assert_eq!(
+            findings,
+            vec![SmallIo {
+                tid: 10,
+                path: "/tmp/app.log".into(),
+                session: 0,
+                op: Op::Write,
+                calls: 100,
+                bytes: 100,
+            }]
+        );
+        assert_eq!(findings[0].bytes_per_call(), 1.0);
+        assert_eq!(findings[0].saved_calls(8 * 1024), 99);
+    }
+
+    #[test]
+    fn test_small_stdout_writes() {
+        let mut trace = [
+            "Attaching 11 probes...",
+            "1000;10;10;execve;/bin/app",
+            "1000;10;10;inherited_fd;1;1;0;/dev/pts/0",
+        ]
+        .map(String::from)
+        .to_vec();
+        for i in 0..20 {
+            let ts = 2000 + i * 100;
+            trace.push(format!("{ts};10;10;write;1;1"));
+            trace.push(format!("{};10;10;write_exit;1", ts + 10));
+        }
+
+        let findings = analyze(&trace).small_io(&SmallIoConfig::default());
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].path, "/dev/pts/0");
+        assert_eq!((findings[0].calls, findings[0].bytes), (20, 20));
+    }
+}
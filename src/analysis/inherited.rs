@@ -0,0 +1,107 @@
+use super::Analysis;
+use crate::syscall::{fd_t, open_flags::*};
+
+/// Formats the access mode, and whether writes append (e.g. `w, append`).
+fn format_flags(flags: u64) -> String {
+    let access = match flags & O_ACCMODE {
+        O_WRONLY => "w",
+        O_RDWR => "rw",
+        _ => "r",
+    };
+
+    if flags & O_APPEND != 0 {
+        format!("{access}, append")
+    } else {
+        access.to_string()
+    }
+}
+
+impl Analysis {
+    /// Returns the bytes read from and written to the inherited fd (and its
+    /// duplicates), summed over all threads.
+    pub fn inherited_bytes(&self, fd: fd_t) -> (usize, usize) {
+        let events = self
+            .threads()
+            .flat_map(|thread| thread.files().values().chain(thread.channels().values()))
+            .flat_map(|info| &info.sessions)
+            .filter(|session| session.inherited == Some(fd))
+            .flat_map(|session| &session.events);
+
+        let (mut read, mut written) = (0, 0);
+        for event in events {
+            if event.is_read() {
+                read += event.bytes();
+            } else if event.is_write() {
+                written += event.bytes();
+            }
+        }
+        (read, written)
+    }
+
+    /// Prints the fds that were open when the trace started, if they were
+    /// recorded.
+    pub fn print_inherited_fds(&self) {
+        use termimad::print_inline as mdprintln;
+
+        if self.inherited_fds().is_empty() {
+            return;
+        }
+
+        let mut table = comfy_table::Table::new();
+        table.set_header(vec!["Fd", "Path", "Flags", "Position", "Read", "Write"]);
+        for info in self.inherited_fds() {
+            let (read, written) = self.inherited_bytes(info.fd);
+            table.add_row(vec![
+                info.fd.to_string(),
+                info.path.clone(),
+                format_flags(info.flags),
+                info.pos.to_string(),
+                read.to_string(),
+                written.to_string(),
+            ]);
+        }
+
+        mdprintln(&format!(
+            "\n# **Inherited fds: {}**\n\n",
+            self.inherited_fds().len()
+        ));
+        println!("{table}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::analyze;
+
+    #[test]
+    fn test_inherited_bytes() {
+        let analysis = analyze(&[
+            "Attaching 17 probes...",
+            "1000;10;10;attach;/usr/bin/daemon",
+            "1000;10;10;inherited_fd;1;1;0;/dev/pts/0",
+            "1000;10;10;inherited_fd;2;1;0;/dev/pts/0",
+            "1000;10;10;inherited_fd;3;1025;8192;/var/log/daemon.log",
+            // stdout and stderr refer to the same terminal
+            "1050;10;10;write;2;10;daemon",
+            "1060;10;10;write_exit;10;daemon",
+            "1100;10;11;write;3;100;worker",
+            "1200;10;11;write_exit;100;worker",
+            "1300;10;12;write;3;50;worker",
+            "1400;10;12;write_exit;50;worker",
+            // A new session of the same file isn't inherited
+            "1500;10;12;openat;4294967196;/var/log/daemon.log;1025;worker",
+            "1600;10;12;openat_exit;4;worker",
+            "1700;10;12;write;4;20;worker",
+            "1800;10;12;write_exit;20;worker",
+        ]);
+
+        let fds = analysis.inherited_fds();
+        assert_eq!(fds.len(), 3);
+        assert_eq!(format_flags(fds[2].flags), "w, append");
+        assert_eq!(fds[2].pos, 8192);
+        assert_eq!(analysis.inherited_bytes(3), (0, 150));
+        assert_eq!(analysis.inherited_bytes(1), (0, 0));
+        assert_eq!(analysis.inherited_bytes(2), (0, 10));
+    }
+}
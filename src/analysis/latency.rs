@@ -0,0 +1,276 @@
+use super::{
+    file::{FileEvent, FileInfo, FileSession},
+    thread::ThreadAnalysis,
+    utils, Analysis,
+};
+use itertools::Itertools;
+use std::{collections::BTreeMap, fmt};
+
+/// The syscalls whose latency is tracked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(test, derive(serde::Serialize))]
+pub enum Op {
+    Open,
+    Read,
+    Write,
+    Close,
+    /// `fsync`, `fdatasync` and `sync_file_range`.
+    Sync,
+    Truncate,
+    Allocate,
+}
+
+impl Op {
+    pub const fn name(self) -> &'static str {
+        match self {
+            Op::Open => "open",
+            Op::Read => "read",
+            Op::Write => "write",
+            Op::Close => "close",
+            Op::Sync => "sync",
+            Op::Truncate => "ftruncate",
+            Op::Allocate => "fallocate",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+#[cfg_attr(test, derive(serde::Serialize))]
+pub struct LatencyStats {
+    pub count: usize,
+    pub min: u64,
+    pub p50: u64,
+    pub p90: u64,
+    pub p99: u64,
+    pub max: u64,
+}
+
+/// The latencies (in ns) of the syscalls, grouped by operation.
+#[derive(Debug, Default)]
+pub struct Latencies {
+    samples: BTreeMap<Op, Vec<u64>>,
+}
+
+impl Latencies {
+    pub fn from_sessions<'a>(sessions: impl IntoIterator<Item = &'a FileSession>) -> Self {
+        let mut latencies = Self::default();
+        for session in sessions {
+            latencies.add_session(session);
+        }
+        latencies
+    }
+
+    pub fn add_session(&mut self, session: &FileSession) {
+        if let Some(latency) = session.open_latency() {
+            self.add(Op::Open, latency);
+        }
+        for event in &session.events {
+            let op = match event {
+                FileEvent::Sync { .. } => Op::Sync,
+                FileEvent::Truncate { .. } => Op::Truncate,
+                FileEvent::Allocate { .. } => Op::Allocate,
+                _ if event.is_read() => Op::Read,
+                _ => Op::Write,
+            };
+            self.add(op, event.latency());
+        }
+        if let Some(latency) = session.close_latency() {
+            self.add(Op::Close, latency);
+        }
+    }
+
+    pub fn add(&mut self, op: Op, latency: u64) {
+        self.samples.entry(op).or_default().push(latency);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    pub fn stats(&self, op: Op) -> Option<LatencyStats> {
+        let mut samples = self.samples.get(&op)?.clone();
+        samples.sort_unstable();
+
+        Some(LatencyStats {
+            count: samples.len(),
+            min: *samples.first()?,
+            p50: utils::percentile(&samples, 0.50),
+            p90: utils::percentile(&samples, 0.90),
+            p99: utils::percentile(&samples, 0.99),
+            max: *samples.last()?,
+        })
+    }
+
+    pub fn histogram(&self, op: Op) -> Option<Histogram> {
+        Some(Histogram::new(self.samples.get(&op)?))
+    }
+
+    /// Returns the operations that have at least one sample.
+    pub fn ops(&self) -> impl Iterator<Item = Op> + '_ {
+        self.samples.keys().copied()
+    }
+
+    pub fn table(&self) -> comfy_table::Table {
+        let mut table = comfy_table::Table::new();
+        table.set_header(vec!["", "Count", "Min", "p50", "p90", "p99", "Max"]);
+        for op in self.ops() {
+            let Some(stats) = self.stats(op) else {
+                continue;
+            };
+            table.add_row(vec![
+                op.name().to_string(),
+                stats.count.to_string(),
+                utils::format_ns(stats.min),
+                utils::format_ns(stats.p50),
+                utils::format_ns(stats.p90),
+                utils::format_ns(stats.p99),
+                utils::format_ns(stats.max),
+            ]);
+        }
+        table
+    }
+}
+
+/// A power-of-two histogram, rendered like the `hist()` maps of bpftrace.
+#[derive(Debug, PartialEq)]
+pub struct Histogram {
+    /// Bucket 0 holds the value 0, bucket `i` the values in `[2^(i-1), 2^i)`.
+    buckets: Vec<usize>,
+}
+
+impl Histogram {
+    pub fn new(values: &[u64]) -> Self {
+        let mut buckets = vec![0; 65];
+        for &value in values {
+            buckets[(u64::BITS - value.leading_zeros()) as usize] += 1;
+        }
+        Self { buckets }
+    }
+
+    fn label(value: u64) -> String {
+        const SUFFIXES: [&str; 7] = ["", "K", "M", "G", "T", "P", "E"];
+
+        let mut value = value;
+        let mut suffix = 0;
+        while value >= 1024 && value.is_multiple_of(1024) {
+            value /= 1024;
+            suffix += 1;
+        }
+        format!("{value}{}", SUFFIXES[suffix])
+    }
+}
+
+impl fmt::Display for Histogram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const WIDTH: usize = 52;
+
+        let Some(first) = self.buckets.iter().position(|&c| c > 0) else {
+            return Ok(());
+        };
+        let last = self.buckets.iter().rposition(|&c| c > 0).unwrap_or(first);
+        let max = self.buckets.iter().copied().max().unwrap_or(1);
+
+        for (i, &count) in self.buckets.iter().enumerate().take(last + 1).skip(first) {
+            let range = match i {
+                0 => "[0]".to_string(),
+                1 => "[1]".to_string(),
+                64 => format!("[{}, ...)", Self::label(1 << 63)),
+                _ => format!("[{}, {})", Self::label(1 << (i - 1)), Self::label(1 << i)),
+            };
+            let bar = "@".repeat((count * WIDTH).div_ceil(max));
+            writeln!(f, "{range:<16} {count:>8} |{bar:<WIDTH$}|")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FileInfo {
+    pub fn latencies(&self) -> Latencies {
+        Latencies::from_sessions(&self.sessions)
+    }
+}
+
+impl ThreadAnalysis {
+    pub fn latencies(&self) -> Latencies {
+        Latencies::from_sessions(self.files().values().flat_map(|info| &info.sessions))
+    }
+}
+
+impl Analysis {
+    /// Prints the latency distribution of each thread, followed by the
+    /// latencies of each of its files.
+    pub fn print_latency(&self) {
+        use termimad::print_inline as mdprintln;
+
+        for thread in self.threads() {
+            let latencies = thread.latencies();
+            if latencies.is_empty() {
+                continue;
+            }
+
+            mdprintln(&format!("\n# **Latency: Thread {}**\n\n", thread.label()));
+            println!("{}", latencies.table());
+            for op in latencies.ops() {
+                if let Some(histogram) = latencies.histogram(op) {
+                    println!("\n@{} (ns):\n{histogram}", op.name());
+                }
+            }
+
+            for (path, info) in thread.files().iter().sorted_by_key(|(path, _)| *path) {
+                let latencies = info.latencies();
+                if latencies.is_empty() {
+                    continue;
+                }
+
+                mdprintln(&format!("\n## File: **{}**\n\n", path));
+                println!("{}", latencies.table());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tracer::BpfTracer;
+
+    #[test]
+    fn test_histogram() {
+        let histogram = Histogram::new(&[0, 1, 3, 1000, 1024, 1500, 3000]);
+        insta::assert_snapshot!(histogram.to_string(), @r"
+        [0]                     1 |@@@@@@@@@@@@@@@@@@@@@@@@@@                          |
+        [1]                     1 |@@@@@@@@@@@@@@@@@@@@@@@@@@                          |
+        [2, 4)                  1 |@@@@@@@@@@@@@@@@@@@@@@@@@@                          |
+        [4, 8)                  0 |                                                    |
+        [8, 16)                 0 |                                                    |
+        [16, 32)                0 |                                                    |
+        [32, 64)                0 |                                                    |
+        [64, 128)               0 |                                                    |
+        [128, 256)              0 |                                                    |
+        [256, 512)              0 |                                                    |
+        [512, 1K)               1 |@@@@@@@@@@@@@@@@@@@@@@@@@@                          |
+        [1K, 2K)                2 |@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@|
+        [2K, 4K)                1 |@@@@@@@@@@@@@@@@@@@@@@@@@@                          |
+        ");
+    }
+
+    #[test]
+    fn test_latency_threaded() {
+        let raw_trace = include_str!("../../data/threaded.txt");
+        let analysis = Analysis::new(BpfTracer::parse_trace(raw_trace).unwrap());
+
+        let stats = analysis
+            .threads()
+            .map(|thread| {
+                let latencies = thread.latencies();
+                let stats = latencies
+                    .ops()
+                    .map(|op| (op, latencies.stats(op).unwrap()))
+                    .collect::<Vec<_>>();
+                (thread.tid(), stats)
+            })
+            .collect::<Vec<_>>();
+        insta::assert_json_snapshot!(stats);
+    }
+}
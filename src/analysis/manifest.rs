@@ -0,0 +1,280 @@
+use super::{utils, Analysis};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Component, Path, PathBuf},
+    str::FromStr,
+};
+
+/// Maximum number of symlinks that are followed for a single path, like
+/// `MAXSYMLINKS` of the kernel.
+const MAX_SYMLINKS: usize = 40;
+
+/// The output format of the manifest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ManifestFormat {
+    /// One path per line, for `tar --no-recursion -T <file>`.
+    Tar,
+    /// `COPY` instructions of a Dockerfile.
+    Copy,
+}
+
+impl ManifestFormat {
+    pub const VARIANTS: &'static [&'static str] = &["tar", "copy"];
+}
+
+impl FromStr for ManifestFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tar" => Ok(ManifestFormat::Tar),
+            "copy" => Ok(ManifestFormat::Copy),
+            _ => Err(anyhow::anyhow!("Unknown manifest format: {s}")),
+        }
+    }
+}
+
+/// The files, symlinks and directories that are needed to provide a set of
+/// paths in a minimal filesystem.
+#[derive(Debug, Default, PartialEq)]
+pub struct Manifest {
+    pub files: BTreeSet<PathBuf>,
+    /// The symlinks on the way to the files, with their (unresolved) target.
+    pub symlinks: BTreeMap<PathBuf, PathBuf>,
+    /// The parent directories of the files and symlinks.
+    pub dirs: BTreeSet<PathBuf>,
+    /// Paths that no longer exist (e.g. temporary files).
+    pub missing: BTreeSet<PathBuf>,
+}
+
+impl Manifest {
+    /// Resolves the paths in the filesystem below `root`, e.g. `/` for the
+    /// host or the root of an unpacked image.
+    pub fn new(paths: impl IntoIterator<Item = PathBuf>, root: &Path) -> Self {
+        let mut manifest = Manifest::default();
+        for path in paths {
+            manifest.add(&path, root, 0);
+        }
+
+        let parents = manifest
+            .files
+            .iter()
+            .chain(manifest.symlinks.keys())
+            .flat_map(|path| path.ancestors().skip(1))
+            .filter(|dir| dir.parent().is_some())
+            .map(Path::to_path_buf)
+            .collect::<Vec<_>>();
+        manifest.dirs.extend(parents);
+
+        manifest
+    }
+
+    fn add(&mut self, path: &Path, root: &Path, depth: usize) {
+        if depth > MAX_SYMLINKS {
+            log::warn!("Too many levels of symbolic links: {}", path.display());
+            return;
+        }
+
+        let components = path.components().collect::<Vec<_>>();
+        let mut resolved = PathBuf::from("/");
+        for (i, component) in components.iter().enumerate() {
+            match component {
+                Component::Normal(name) => {
+                    let next = resolved.join(name);
+                    let host = root.join(next.strip_prefix("/").unwrap_or(&next));
+                    if let Ok(target) = std::fs::read_link(&host) {
+                        // Absolute targets replace the resolved path
+                        let target_path = utils::clean_path(&resolved.join(&target));
+                        self.symlinks.insert(next, target);
+
+                        let rest = components[i + 1..].iter().collect::<PathBuf>();
+                        return self.add(&target_path.join(rest), root, depth + 1);
+                    }
+                    resolved = next;
+                }
+                Component::ParentDir => {
+                    resolved.pop();
+                }
+                Component::RootDir | Component::CurDir | Component::Prefix(_) => {}
+            }
+        }
+
+        let host = root.join(resolved.strip_prefix("/").unwrap_or(&resolved));
+        if host.is_dir() {
+            self.dirs.insert(resolved);
+        } else if host.exists() {
+            self.files.insert(resolved);
+        } else {
+            self.missing.insert(resolved);
+        }
+    }
+
+    /// Returns all directories, symlinks and files, parents first.
+    pub fn paths(&self) -> BTreeSet<&Path> {
+        self.dirs
+            .iter()
+            .chain(self.symlinks.keys())
+            .chain(&self.files)
+            .map(PathBuf::as_path)
+            .collect()
+    }
+
+    /// Returns the manifest in the given format. `from` is the stage (or
+    /// image) the files are copied from.
+    pub fn render(&self, format: ManifestFormat, from: &str) -> String {
+        match format {
+            ManifestFormat::Tar => self
+                .paths()
+                .iter()
+                .map(|path| format!("{}\n", path.display()))
+                .collect(),
+            // Directories are created by `COPY`, and copying them would also
+            // copy all of their files. The JSON form allows whitespace in the
+            // paths.
+            ManifestFormat::Copy => self
+                .symlinks
+                .keys()
+                .chain(&self.files)
+                .collect::<BTreeSet<_>>()
+                .iter()
+                .map(|path| {
+                    let path = path
+                        .to_string_lossy()
+                        .replace('\\', "\\\\")
+                        .replace('"', "\\\"");
+                    format!("COPY --from={from} [\"{path}\", \"{path}\"]\n")
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Analysis {
+    /// Returns the absolute paths that were successfully opened, except for
+    /// the ones of pseudo filesystems.
+    pub fn opened_paths(&self) -> BTreeSet<PathBuf> {
+        self.threads()
+            .flat_map(|thread| {
+                thread
+                    .files()
+                    .iter()
+                    .filter(|(_, info)| info.sessions.iter().any(|s| !s.is_inherited()))
+                    .map(|(path, _)| path)
+                    .chain(thread.leaks().iter().map(|session| &session.path))
+            })
+            .map(PathBuf::from)
+            .filter(|path| path.is_absolute() && !utils::is_pseudo_fs(path))
+            .collect()
+    }
+
+    /// Returns the manifest of the opened paths and the executables (see
+    /// [`Manifest::new`]).
+    pub fn manifest(&self, root: &Path) -> Manifest {
+        let executables = self
+            .executables(root)
+            .into_iter()
+            .map(PathBuf::from)
+            .filter(|path| path.is_absolute());
+        Manifest::new(self.opened_paths().into_iter().chain(executables), root)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+
+    #[test]
+    fn test_manifest_symlinks() {
+        let root = tempfile::tempdir().unwrap();
+        let create = |path: &str| {
+            let path = root.path().join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        };
+        create("usr/lib/libc.so.6");
+        create("usr/bin/vi");
+        create("etc/hosts");
+        symlink("usr/lib", root.path().join("lib")).unwrap();
+        std::fs::create_dir_all(root.path().join("etc/alternatives")).unwrap();
+        symlink("/usr/bin/vi", root.path().join("etc/alternatives/editor")).unwrap();
+
+        let paths = [
+            "/lib/libc.so.6",
+            "/etc/hosts",
+            "/etc/alternatives/editor",
+            "/tmp/gone",
+        ];
+        let manifest = Manifest::new(paths.map(PathBuf::from), root.path());
+
+        assert_eq!(manifest.missing, BTreeSet::from(["/tmp/gone".into()]));
+        assert_eq!(
+            manifest.render(ManifestFormat::Tar, "rootfs"),
+            [
+                "/etc",
+                "/etc/alternatives",
+                "/etc/alternatives/editor",
+                "/etc/hosts",
+                "/lib",
+                "/usr",
+                "/usr/bin",
+                "/usr/bin/vi",
+                "/usr/lib",
+                "/usr/lib/libc.so.6",
+            ]
+            .map(|line| format!("{line}\n"))
+            .concat()
+        );
+        assert!(manifest.render(ManifestFormat::Copy, "rootfs").starts_with(
+            "COPY --from=rootfs [\"/etc/alternatives/editor\", \"/etc/alternatives/editor\"]\n"
+        ));
+    }
+
+    #[test]
+    fn test_manifest_copy_quoting() {
+        let root = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(root.path().join("srv/my data")).unwrap();
+        std::fs::write(root.path().join("srv/my data/input.txt"), "").unwrap();
+        std::fs::write(root.path().join("srv/\"q\\.txt"), "").unwrap();
+
+        let paths = ["/srv/my data/input.txt", "/srv/\"q\\.txt"];
+        let manifest = Manifest::new(paths.map(PathBuf::from), root.path());
+
+        assert_eq!(
+            manifest.render(ManifestFormat::Copy, "rootfs"),
+            [
+                r#"COPY --from=rootfs ["/srv/\"q\\.txt", "/srv/\"q\\.txt"]"#,
+                r#"COPY --from=rootfs ["/srv/my data/input.txt", "/srv/my data/input.txt"]"#,
+            ]
+            .map(|line| format!("{line}\n"))
+            .concat()
+        );
+    }
+
+    #[test]
+    fn test_manifest_executables() {
+        let root = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(root.path().join("bin")).unwrap();
+
+        // The headers of the test binary are enough to find its interpreter
+        let exe = std::fs::read(std::env::current_exe().unwrap()).unwrap();
+        std::fs::write(
+            root.path().join("bin/app"),
+            &exe[..exe.len().min(64 * 1024)],
+        )
+        .unwrap();
+        let interp = crate::elf::interpreter(&root.path().join("bin/app")).unwrap();
+        let host_interp = root.path().join(interp.trim_start_matches('/'));
+        std::fs::create_dir_all(host_interp.parent().unwrap()).unwrap();
+        std::fs::write(host_interp, "").unwrap();
+
+        let analysis =
+            crate::analysis::analyze(&["Attaching 13 probes...", "1000;10;10;execve;/bin/app"]);
+        let manifest = analysis.manifest(root.path());
+
+        assert_eq!(
+            manifest.files,
+            BTreeSet::from(["/bin/app".into(), interp.into()])
+        );
+    }
+}
@@ -0,0 +1,205 @@
+use super::{utils, Analysis};
+use std::collections::BTreeMap;
+
+/// The metadata syscalls, which work on paths without opening them, and the
+/// directory listings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(test, derive(serde::Serialize))]
+pub enum MetadataKind {
+    /// `statx` and `newfstatat`.
+    Stat,
+    /// `access`, `faccessat` and `faccessat2`.
+    Access,
+    /// `readlink` and `readlinkat`.
+    Readlink,
+    /// `getdents64` on a directory fd.
+    Getdents,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(test, derive(serde::Serialize))]
+pub struct MetadataOp {
+    pub kind: MetadataKind,
+    /// The return value, negative errno if the syscall failed.
+    pub ret: i64,
+    pub start_ts: u64,
+    pub end_ts: u64,
+}
+
+impl MetadataOp {
+    pub const fn latency(&self) -> u64 {
+        self.end_ts - self.start_ts
+    }
+}
+
+/// The metadata operations on a path, aggregated across all threads.
+#[derive(Debug, Default, PartialEq)]
+#[cfg_attr(test, derive(serde::Serialize))]
+pub struct MetadataSummary {
+    pub path: String,
+    pub stats: usize,
+    pub accesses: usize,
+    pub readlinks: usize,
+    /// Number of `getdents64` calls, including the last one at the end of the
+    /// directory.
+    pub listings: usize,
+    pub failures: usize,
+    /// Cumulative time spent in the syscalls (in ns).
+    pub duration: u64,
+    /// The slowest syscall (in ns).
+    pub max_latency: u64,
+}
+
+impl MetadataSummary {
+    pub const fn calls(&self) -> usize {
+        self.stats + self.accesses + self.readlinks + self.listings
+    }
+}
+
+impl Analysis {
+    /// Returns the metadata operations by path, with the most calls first.
+    /// Ties are ordered by path.
+    pub fn metadata_summary(&self) -> Vec<MetadataSummary> {
+        let mut paths = BTreeMap::<&str, MetadataSummary>::new();
+        for thread in self.threads() {
+            for (path, ops) in thread.metadata() {
+                let summary = paths.entry(path).or_insert_with(|| MetadataSummary {
+                    path: path.clone(),
+                    ..Default::default()
+                });
+                for op in ops {
+                    match op.kind {
+                        MetadataKind::Stat => summary.stats += 1,
+                        MetadataKind::Access => summary.accesses += 1,
+                        MetadataKind::Readlink => summary.readlinks += 1,
+                        MetadataKind::Getdents => summary.listings += 1,
+                    }
+                    summary.failures += usize::from(op.ret < 0);
+                    summary.duration += op.latency();
+                    summary.max_latency = summary.max_latency.max(op.latency());
+                }
+            }
+        }
+
+        let mut paths = paths.into_values().collect::<Vec<_>>();
+        paths.sort_by(|a, b| b.calls().cmp(&a.calls()).then_with(|| a.path.cmp(&b.path)));
+
+        paths
+    }
+
+    /// Prints the `top` paths with the most metadata operations.
+    pub fn print_metadata(&self, top: usize) {
+        use termimad::print_inline as mdprintln;
+
+        let paths = self.metadata_summary();
+        if paths.is_empty() {
+            return;
+        }
+
+        let mut table = comfy_table::Table::new();
+        table.set_header(vec![
+            "Path",
+            "Stat",
+            "Access",
+            "Readlink",
+            "Getdents",
+            "Failures",
+            "Duration",
+            "Max latency",
+        ]);
+        for path in paths.iter().take(top) {
+            table.add_row(vec![
+                path.path.clone(),
+                path.stats.to_string(),
+                path.accesses.to_string(),
+                path.readlinks.to_string(),
+                path.listings.to_string(),
+                path.failures.to_string(),
+                format!("{:.2} ms", utils::ns_to_ms(path.duration as f64)),
+                format!("{:.2} ms", utils::ns_to_ms(path.max_latency as f64)),
+            ]);
+        }
+
+        mdprintln(&format!(
+            "\n# **Metadata operations: top {} of {} paths**\n\n",
+            top.min(paths.len()),
+            paths.len()
+        ));
+        println!("{table}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::analysis::{analyze, idle::IdleConfig};
+
+    #[test]
+    fn test_metadata_summary() {
+        let analysis = analyze(&[
+            "Attaching 80 probes...",
+            "1000;10;10;execve;/usr/bin/python3;bash",
+            // Probing the search path, without opening anything
+            "1100;10;10;stat;4294967196;/usr/lib/python3/json;python3",
+            "1200;10;10;metadata_exit;0;python3",
+            "1300;10;10;stat;4294967196;/usr/lib/python3/json/__init__.py;python3",
+            "1400;10;10;metadata_exit;0;python3",
+            "1500;10;10;access;4294967196;/usr/local/lib/python3/json;python3",
+            "1600;10;10;metadata_exit;-2;python3",
+            "1700;10;10;readlink;4294967196;/proc/self/exe;python3",
+            "1800;10;10;metadata_exit;15;python3",
+            // Listing a directory, which is also stat'ed through its fd
+            "1900;10;10;openat;4294967196;/usr/lib/python3;591872;python3",
+            "2000;10;10;openat_exit;3;python3",
+            "2100;10;10;stat;3;;python3",
+            "2200;10;10;metadata_exit;0;python3",
+            "2300;10;10;getdents;3;python3",
+            "2400;10;10;getdents_exit;4096;python3",
+            "2500;10;10;getdents;3;python3",
+            "3500;10;10;getdents_exit;0;python3",
+            "3600;10;10;stat;3;json;python3",
+            "3700;10;10;metadata_exit;0;python3",
+            "3800;10;10;close;3;python3",
+            "3900;10;10;close_exit;0;python3",
+        ]);
+
+        insta::assert_json_snapshot!(analysis.metadata_summary());
+    }
+
+    #[test]
+    fn test_metadata_dir_use() {
+        let analysis = analyze(&[
+            "Attaching 80 probes...",
+            "1000;10;10;execve;/usr/bin/python3;bash",
+            // Listed
+            "1100;10;10;openat;4294967196;/usr/lib/python3;591872;python3",
+            "1200;10;10;openat_exit;3;python3",
+            "1300;10;10;getdents;3;python3",
+            "1400;10;10;getdents_exit;4096;python3",
+            "1500;10;10;getdents;3;python3",
+            "1600;10;10;getdents_exit;0;python3",
+            "1700;10;10;close;3;python3",
+            "1800;10;10;close_exit;0;python3",
+            // Used as the dirfd of a stat
+            "1900;10;10;openat;4294967196;/usr/lib/python3/json;591872;python3",
+            "2000;10;10;openat_exit;3;python3",
+            "2100;10;10;stat;3;__init__.py;python3",
+            "2200;10;10;metadata_exit;0;python3",
+            "2300;10;10;close;3;python3",
+            "2400;10;10;close_exit;0;python3",
+            // Not used, the dirfd is ignored for absolute paths
+            "2500;10;10;openat;4294967196;/usr/lib/python3/xml;591872;python3",
+            "2600;10;10;openat_exit;3;python3",
+            "2700;10;10;stat;3;/etc/hosts;python3",
+            "2800;10;10;metadata_exit;0;python3",
+            "2900;10;10;close;3;python3",
+            "3000;10;10;close_exit;0;python3",
+        ]);
+
+        let unused = analysis
+            .idle_sessions(&IdleConfig::default())
+            .into_iter()
+            .map(|f| f.path)
+            .collect::<Vec<_>>();
+        assert_eq!(unused, ["/usr/lib/python3/xml"]);
+    }
+}
@@ -1,41 +1,194 @@
-use crate::syscall::{tid_t, Syscall};
+use crate::{
+    elf,
+    fdinfo::FdInfo,
+    syscall::{open_flags::O_WRONLY, tid_t, RawSyscall, Syscall},
+};
+use filter::PathFilter;
 use itertools::Itertools;
-use std::collections::HashMap;
+use std::{
+    collections::{BTreeSet, HashMap},
+    path::Path,
+};
 use thread::ThreadAnalysis;
 
+pub mod budget;
+pub mod callsite;
+pub mod channels;
+pub mod deps;
+pub mod diff;
+pub mod durability;
 pub mod file;
+pub mod filter;
+pub mod idle;
+pub mod inefficient;
+pub mod inherited;
+pub mod latency;
+pub mod manifest;
+pub mod metadata;
+pub mod policy;
+pub mod reread;
+pub mod stats;
+pub mod summary;
 pub mod thread;
-mod utils;
+pub mod tree;
+pub mod utils;
 
 #[derive(Debug)]
 #[cfg_attr(test, derive(serde::Serialize))]
 pub struct Analysis {
+    /// Timestamp of the first traced syscall.
+    start_ts: u64,
     threads: HashMap<tid_t, ThreadAnalysis>,
+    /// The fds that were open when the trace started, if they were recorded.
+    #[cfg_attr(test, serde(skip_serializing_if = "Vec::is_empty"))]
+    inherited: Vec<FdInfo>,
 }
 
 impl Analysis {
     pub fn new(syscalls: Vec<Syscall>) -> Self {
-        let threads = syscalls
+        let start_ts = syscalls.iter().map(|s| s.ts).min().unwrap_or_default();
+        let inherited = syscalls
+            .iter()
+            .filter_map(|s| match &s.raw {
+                RawSyscall::InheritedFd {
+                    fd,
+                    flags,
+                    pos,
+                    path,
+                } => Some(FdInfo {
+                    fd: *fd,
+                    path: path.clone(),
+                    flags: *flags,
+                    pos: *pos,
+                }),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        // Traces that were recorded without the fds are assumed to inherit
+        // the standard streams.
+        let fd_table = if inherited.is_empty() {
+            ["/dev/stdin", "/dev/stdout", "/dev/stderr"]
+                .into_iter()
+                .zip(0..)
+                .map(|(path, fd)| FdInfo {
+                    fd,
+                    path: path.to_string(),
+                    flags: if fd == 0 { 0 } else { O_WRONLY },
+                    pos: 0,
+                })
+                .collect()
+        } else {
+            inherited.clone()
+        };
+        let fd_table = fd_table.into_iter().map(|info| (info.fd, info)).collect();
+
+        let mut threads = syscalls
             .into_iter()
             .into_group_map_by(|s| s.tid)
             .into_iter()
-            .map(|(tid, syscalls)| (tid, ThreadAnalysis::new(tid, &syscalls)))
-            .collect();
+            .map(|(tid, syscalls)| (tid, ThreadAnalysis::new(tid, &syscalls, &fd_table)))
+            .collect::<HashMap<_, _>>();
+
+        // Any thread of the process can unmap or sync the mappings of another.
+        let mut ranges = HashMap::<_, (Vec<_>, Vec<_>)>::new();
+        for thread in threads.values() {
+            let (unmaps, syncs) = thread.memory_ranges();
+            let entry = ranges.entry(thread.pid()).or_default();
+            entry.0.extend_from_slice(unmaps);
+            entry.1.extend_from_slice(syncs);
+        }
+        for thread in threads.values_mut() {
+            let (unmaps, syncs) = &ranges[&thread.pid()];
+            thread.resolve_mappings(unmaps, syncs);
+        }
+
+        Self {
+            start_ts,
+            threads,
+            inherited,
+        }
+    }
+
+    /// Returns the fds that were open when the trace started, ordered by fd.
+    /// Empty if they weren't recorded.
+    pub fn inherited_fds(&self) -> &[FdInfo] {
+        &self.inherited
+    }
+
+    /// Removes the sessions of inherited fds, e.g. stdout.
+    pub fn remove_inherited(&mut self) {
+        for thread in self.threads.values_mut() {
+            thread.remove_inherited();
+        }
+    }
+
+    /// Removes all files that don't match the filter.
+    pub fn retain_files(&mut self, filter: &PathFilter) {
+        for thread in self.threads.values_mut() {
+            thread.retain_files(|path| filter.matches(path));
+        }
+    }
+
+    /// Returns the executed programs and their ELF interpreters (e.g.
+    /// `/lib64/ld-linux-x86-64.so.2`), which are needed to start them. The
+    /// interpreters are read from the programs below `root` (see
+    /// [`Manifest::new`](manifest::Manifest::new)).
+    pub fn executables(&self, root: &Path) -> BTreeSet<String> {
+        let programs = self
+            .threads()
+            .flat_map(|thread| thread.execs())
+            .cloned()
+            .collect::<BTreeSet<_>>();
+        let interpreters = programs
+            .iter()
+            .filter_map(|program| elf::interpreter(&root.join(program.trim_start_matches('/'))))
+            .collect::<Vec<_>>();
+
+        programs.into_iter().chain(interpreters).collect()
+    }
+
+    pub const fn start_ts(&self) -> u64 {
+        self.start_ts
+    }
+
+    /// Returns the analyzed threads, ordered by thread id.
+    pub fn threads(&self) -> impl Iterator<Item = &ThreadAnalysis> {
+        self.threads.values().sorted_by_key(|t| t.tid())
+    }
 
-        Self { threads }
+    pub fn thread(&self, tid: tid_t) -> Option<&ThreadAnalysis> {
+        self.threads.get(&tid)
+    }
+
+    /// Returns the label of the thread (see [`ThreadAnalysis::label`]).
+    pub fn thread_label(&self, tid: tid_t) -> String {
+        self.thread(tid)
+            .map(|t| t.label())
+            .unwrap_or_else(|| tid.to_string())
     }
 
     pub fn print_result(&self) {
-        for thread in self.threads.values() {
+        for thread in self.threads() {
             thread.print_result();
         }
     }
 }
 
+/// Analyzes a trace given as lines, as they are printed by bpftrace.
+#[cfg(test)]
+pub fn analyze(lines: &[impl AsRef<str>]) -> Analysis {
+    let trace = lines
+        .iter()
+        .map(|line| format!("{}\n", line.as_ref()))
+        .collect::<String>();
+    Analysis::new(crate::tracer::BpfTracer::parse_trace(&trace).unwrap())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::tracer::BpfTracer;
+    use crate::{analysis::file::FileEvent, tracer::BpfTracer};
 
     #[test]
     fn test_analyze_multisession() {
@@ -58,4 +211,184 @@ mod tests {
             insta::assert_json_snapshot!(analysis);
         });
     }
+
+    #[test]
+    fn test_thread_names() {
+        let analysis = analyze(&[
+            "Attaching 13 probes...",
+            "1000;10;10;execve;/bin/app;bash",
+            "1100;10;10;openat;4294967196;/etc/hosts;524288;app",
+            "1200;10;10;openat_exit;3;app",
+            "1300;10;11;set_name;worker-1;app",
+            "1400;10;11;read;3;4096;worker-1",
+            "1500;10;11;read_exit;326;worker-1",
+            "1600;10;12;read;3;4096",
+            "1700;10;12;read_exit;0",
+        ]);
+        assert_eq!(analysis.thread_label(10), "10 (app)");
+        assert_eq!(analysis.thread_label(11), "11 (app -> worker-1)");
+        assert_eq!(analysis.thread(11).unwrap().name(), Some("worker-1"));
+        assert_eq!(analysis.thread_label(12), "12");
+    }
+
+    #[test]
+    fn test_inherited_fds() {
+        let mut analysis = analyze(&[
+            "Attaching 17 probes...",
+            "1000;10;10;execve;/bin/app;bash",
+            "1000;10;10;inherited_fd;1;2;0;/dev/pts/3",
+            "1000;10;10;inherited_fd;2;2;0;/dev/pts/3",
+            "1000;10;10;inherited_fd;7;1025;0;/var/log/parent.log",
+            "1100;10;10;write;1;12;app",
+            "1200;10;10;write_exit;12;app",
+            // Redirects stderr to the log file of the parent
+            "1300;10;10;dup2;7;2;app",
+            "1400;10;10;dup_exit;2;app",
+            "1500;10;10;write;2;100;app",
+            "1600;10;10;write_exit;100;app",
+            "1700;10;10;close;1;app",
+            "1800;10;10;close_exit;0;app",
+            "1900;10;10;openat;4294967196;/etc/hosts;524288;app",
+            "2000;10;10;openat_exit;1;app",
+            "2100;10;10;read;1;4096;app",
+            "2200;10;10;read_exit;326;app",
+            "2300;10;10;close;1;app",
+            "2400;10;10;close_exit;0;app",
+        ]);
+        let thread = analysis.thread(10).unwrap();
+        let sessions = |path: &str| {
+            thread.files()[path]
+                .sessions
+                .iter()
+                .map(|s| (s.open_ts, s.close_ts, s.events.len(), s.inherited))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(sessions("/dev/pts/3"), [(1000, 1700, 1, Some(1))]);
+        assert_eq!(
+            sessions("/var/log/parent.log"),
+            [(1300, 2400, 1, Some(7)), (1000, 2400, 0, Some(7))]
+        );
+        assert_eq!(sessions("/etc/hosts"), [(1900, 2300, 1, None)]);
+        assert!(thread.leaks().is_empty());
+
+        analysis.remove_inherited();
+        let files = analysis.thread(10).unwrap().files();
+        assert_eq!(files.keys().collect::<Vec<_>>(), ["/etc/hosts"]);
+    }
+
+    #[test]
+    fn test_transfers() {
+        let analysis = analyze(&[
+            "Attaching 50 probes...",
+            "1000;10;10;execve;/usr/bin/cp;bash",
+            "1100;10;10;openat;4294967196;/data/in.bin;524288;cp",
+            "1200;10;10;openat_exit;3;cp",
+            "1300;10;10;openat;4294967196;/data/out.bin;524865;cp",
+            "1400;10;10;openat_exit;4;cp",
+            "1500;10;10;transfer;3;4;1073741824;cp",
+            "1600;10;10;transfer_exit;65536;cp",
+            "1700;10;10;read;3;16;cp",
+            "1800;10;10;read_exit;16;cp",
+            // EOF
+            "1900;10;10;transfer;3;4;1073741824;cp",
+            "2000;10;10;transfer_exit;0;cp",
+            // A file that is sent to a socket
+            "2100;10;10;socket;2;1;cp",
+            "2200;10;10;socket_exit;5;cp",
+            "2300;10;10;transfer;3;5;4096;cp",
+            "2400;10;10;transfer_exit;4096;cp",
+            "2500;10;10;close;3;cp",
+            "2600;10;10;close_exit;0;cp",
+            "2700;10;10;close;4;cp",
+            "2800;10;10;close_exit;0;cp",
+        ]);
+        let thread = analysis.thread(10).unwrap();
+        assert_eq!(
+            thread.files()["/data/in.bin"].total_bytes(),
+            (65536 + 16 + 4096, 0)
+        );
+        assert_eq!(thread.files()["/data/out.bin"].total_bytes(), (0, 65536));
+        assert_eq!(thread.channels()["tcp"].total_bytes(), (0, 4096));
+
+        let peers = thread.files()["/data/in.bin"].sessions[0]
+            .events
+            .iter()
+            .filter_map(|event| match event {
+                FileEvent::Transfer {
+                    is_source, peer, ..
+                } => Some((*is_source, peer.as_str())),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(peers, [(true, "/data/out.bin"), (true, "tcp")]);
+    }
+
+    #[test]
+    fn test_mappings() {
+        let analysis = analyze(&[
+            "Attaching 60 probes...",
+            "1000;10;10;execve;/usr/bin/app;bash",
+            // The loader maps the whole library, and then its segments over it
+            "1100;10;10;openat;4294967196;/usr/lib/libc.so.6;524288;app",
+            "1200;10;10;openat_exit;3;app",
+            "1300;10;10;read;3;832;app",
+            "1400;10;10;read_exit;832;app",
+            "1500;10;10;mmap;3;2000000;1;2050;0;app",
+            "1600;10;10;mmap_exit;140000000000000;app",
+            "1700;10;10;mmap;3;1000000;5;2066;163840;app",
+            "1800;10;10;mmap_exit;140000000163840;app",
+            "1900;10;10;close;3;app",
+            "2000;10;10;close_exit;0;app",
+            // A shared mapping that is synced and unmapped after the close
+            "2100;10;10;openat;4294967196;/var/lib/app/db;2;app",
+            "2200;10;10;openat_exit;3;app",
+            "2300;10;10;mmap;3;8192;3;1;4096;app",
+            "2400;10;10;mmap_exit;140000010000000;app",
+            "2500;10;10;close;3;app",
+            "2600;10;10;close_exit;0;app",
+            "2700;10;10;msync;140000010000000;4096;4;app",
+            "2800;10;10;msync_exit;0;app",
+            "2900;10;10;munmap;140000010000000;8192;app",
+            "3000;10;10;munmap_exit;0;app",
+            "3100;10;10;msync;140000010000000;4096;4;app",
+            "3200;10;10;msync_exit;-12;app",
+            // Another thread of the process syncs and unmaps the mapping
+            "3300;10;10;openat;4294967196;/var/lib/app/log;2;app",
+            "3400;10;10;openat_exit;3;app",
+            "3500;10;10;mmap;3;4096;3;1;0;app",
+            "3600;10;10;mmap_exit;140000020000000;app",
+            "3700;10;11;msync;140000020000000;4096;4;worker",
+            "3800;10;11;msync_exit;0;worker",
+            "3900;10;11;munmap;140000020000000;4096;worker",
+            "4000;10;11;munmap_exit;0;worker",
+            "4100;10;10;close;3;app",
+            "4200;10;10;close_exit;0;app",
+        ]);
+        let files = analysis.thread(10).unwrap().files();
+
+        let libc = &files["/usr/lib/libc.so.6"];
+        assert_eq!(libc.mapped_bytes(), 2000000);
+        let mappings = &libc.sessions[0].mappings;
+        assert_eq!(mappings[0].format(), "r-- private");
+        assert_eq!(mappings[1].format(), "r-x private");
+        // The fixed mapping partially replaced the first one, which ends the whole
+        // mapping. The fixed one is still mapped.
+        assert_eq!((mappings[0].unmap_ts, mappings[1].unmap_ts), (1700, 0));
+
+        let db = &files["/var/lib/app/db"].sessions[0];
+        assert_eq!(db.mapped_bytes(), 8192);
+        assert_eq!(db.mappings[0].format(), "rw- shared");
+        assert_eq!((db.mappings[0].unmap_ts, db.mappings[0].syncs), (2900, 1));
+        assert!(db.mappings[0].unmap_ts > db.close_ts);
+
+        let log = &files["/var/lib/app/log"].sessions[0];
+        assert_eq!((log.mappings[0].unmap_ts, log.mappings[0].syncs), (3900, 1));
+
+        let summary = analysis.file_summary(summary::SortBy::Bytes);
+        assert_eq!(summary[0].path, "/usr/lib/libc.so.6");
+        assert_eq!(
+            (summary[0].read_bytes, summary[0].mapped_bytes),
+            (832, 2000000)
+        );
+    }
 }
@@ -0,0 +1,389 @@
+use super::{file::FileSession, Analysis};
+use crate::syscall::mmap_flags::PROT_EXEC;
+use itertools::Itertools;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+    str::FromStr,
+};
+
+/// The sandbox the policy is generated for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PolicyFormat {
+    /// Environment of the Landlock sandboxer (`samples/landlock/sandboxer.c`).
+    Landlock,
+    AppArmor,
+    Bwrap,
+}
+
+impl PolicyFormat {
+    pub const VARIANTS: &'static [&'static str] = &["landlock", "apparmor", "bwrap"];
+}
+
+impl FromStr for PolicyFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "landlock" => Ok(PolicyFormat::Landlock),
+            "apparmor" => Ok(PolicyFormat::AppArmor),
+            "bwrap" => Ok(PolicyFormat::Bwrap),
+            _ => Err(anyhow::anyhow!("Unknown policy format: {s}")),
+        }
+    }
+}
+
+/// The access a rule grants, ordered from least to most privileged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(test, derive(serde::Serialize))]
+pub enum Access {
+    ReadOnly,
+    ReadWrite,
+    /// Files can be created (and written) in the directory.
+    Create,
+}
+
+/// A path the program needs access to. Directory rules also grant access to
+/// everything below the directory.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(test, derive(serde::Serialize))]
+pub struct Rule {
+    pub path: String,
+    pub access: Access,
+    pub dir: bool,
+    /// The path is executed, i.e. it's a program or its ELF interpreter.
+    #[cfg_attr(test, serde(skip_serializing_if = "std::ops::Not::not"))]
+    pub exec: bool,
+    /// The path is mapped executable, e.g. a shared library.
+    #[cfg_attr(test, serde(skip_serializing_if = "std::ops::Not::not"))]
+    pub map: bool,
+}
+
+impl Rule {
+    /// Returns whether the rule grants at least the access of `other`.
+    fn covers(&self, other: &Rule) -> bool {
+        self.dir
+            && self.access >= other.access
+            && self.exec >= other.exec
+            && self.map >= other.map
+            && Path::new(&other.path).starts_with(&self.path)
+            && self.path != other.path
+    }
+}
+
+/// A least-privilege allowlist of the paths a program accessed.
+#[derive(Debug, Default)]
+#[cfg_attr(test, derive(serde::Serialize))]
+pub struct Policy {
+    /// The rules, ordered by path (parents first).
+    pub rules: Vec<Rule>,
+    /// Relative paths, which can't be expressed in the policy.
+    pub skipped: BTreeSet<String>,
+}
+
+impl Policy {
+    /// Builds the policy from the opened sessions and the executed programs
+    /// (see [`Analysis::executables`]). If at least `threshold` paths of a
+    /// directory need the same access, they are collapsed into a rule for the
+    /// directory (0 disables collapsing).
+    pub fn build<'a>(
+        sessions: impl Iterator<Item = &'a FileSession>, executables: &BTreeSet<String>,
+        threshold: usize,
+    ) -> Self {
+        let mut policy = Policy::default();
+
+        let mut paths = BTreeMap::<String, Rule>::new();
+        fn grant<'a>(
+            paths: &'a mut BTreeMap<String, Rule>, path: &str, access: Access, dir: bool,
+        ) -> &'a mut Rule {
+            let rule = paths.entry(path.to_string()).or_insert_with(|| Rule {
+                path: path.to_string(),
+                access,
+                dir,
+                exec: false,
+                map: false,
+            });
+            rule.access = rule.access.max(access);
+            rule.dir |= dir;
+            rule
+        }
+        for program in executables {
+            if !program.starts_with('/') {
+                policy.skipped.insert(program.clone());
+                continue;
+            }
+            grant(&mut paths, program, Access::ReadOnly, false).exec = true;
+        }
+        for session in sessions {
+            if !session.path.starts_with('/') {
+                policy.skipped.insert(session.path.clone());
+                continue;
+            }
+
+            // The created file might not exist before the program runs, so the
+            // access is granted on the directory.
+            if session.is_create() {
+                let parent = Path::new(&session.path).parent().unwrap_or(Path::new("/"));
+                grant(&mut paths, &parent.to_string_lossy(), Access::Create, true);
+            } else {
+                let access = if session.is_writable() {
+                    Access::ReadWrite
+                } else {
+                    Access::ReadOnly
+                };
+                let rule = grant(&mut paths, &session.path, access, session.is_directory());
+                rule.map |= session.mappings.iter().any(|m| m.prot & PROT_EXEC != 0);
+            }
+        }
+
+        let mut rules = paths.into_values().collect::<BTreeSet<_>>();
+
+        // Collapse the paths bottom up, so that collapsed directories count
+        // towards their parent.
+        loop {
+            let groups = rules
+                .iter()
+                .filter_map(|rule| {
+                    let parent = Path::new(&rule.path)
+                        .parent()?
+                        .to_string_lossy()
+                        .to_string();
+                    (parent != "/").then_some(((parent, rule.access), rule.clone()))
+                })
+                .into_group_map();
+
+            let Some(((parent, access), children)) = groups
+                .into_iter()
+                .filter(|(_, children)| threshold > 0 && children.len() >= threshold)
+                .max_by_key(|((parent, _), _)| parent.matches('/').count())
+            else {
+                break;
+            };
+
+            let exec = children.iter().any(|child| child.exec);
+            let map = children.iter().any(|child| child.map);
+            for child in children {
+                rules.remove(&child);
+            }
+            rules.insert(Rule {
+                path: parent,
+                access,
+                dir: true,
+                exec,
+                map,
+            });
+        }
+
+        policy.rules = rules
+            .iter()
+            .filter(|rule| !rules.iter().any(|other| other.covers(rule)))
+            .cloned()
+            .collect();
+        policy
+    }
+
+    /// Returns the policy in the given format. `name` is the name of the
+    /// AppArmor profile, or the program that is run by bwrap.
+    pub fn render(&self, format: PolicyFormat, name: &str) -> String {
+        let mut output = String::new();
+        if !self.skipped.is_empty() {
+            output.push_str(&format!(
+                "# Skipped {} relative paths: {}\n",
+                self.skipped.len(),
+                self.skipped.iter().join(", ")
+            ));
+        }
+
+        match format {
+            PolicyFormat::Landlock => {
+                let paths = |f: fn(Access) -> bool| {
+                    self.rules
+                        .iter()
+                        .filter(|rule| f(rule.access))
+                        .map(|rule| rule.path.as_str())
+                        .join(":")
+                };
+                output.push_str(&format!(
+                    "export LL_FS_RO={}\n",
+                    shell_quote(&paths(|a| a == Access::ReadOnly))
+                ));
+                output.push_str(&format!(
+                    "export LL_FS_RW={}\n",
+                    shell_quote(&paths(|a| a != Access::ReadOnly))
+                ));
+            }
+            PolicyFormat::AppArmor => {
+                output.push_str("#include <tunables/global>\n\n");
+                output.push_str(&format!("profile {name} {{\n"));
+                for rule in &self.rules {
+                    let mode = format!(
+                        "{}{}{}",
+                        if rule.map { "m" } else { "" },
+                        match rule.access {
+                            Access::ReadOnly => "r",
+                            Access::ReadWrite | Access::Create => "rw",
+                        },
+                        if rule.exec { "ix" } else { "" }
+                    );
+                    if rule.dir {
+                        let dir = rule.path.trim_end_matches('/');
+                        output.push_str(&format!("  {} r,\n", apparmor_quote(&format!("{dir}/"))));
+                        output.push_str(&format!(
+                            "  {} {mode},\n",
+                            apparmor_quote(&format!("{dir}/**"))
+                        ));
+                    } else {
+                        output.push_str(&format!("  {} {mode},\n", apparmor_quote(&rule.path)));
+                    }
+                }
+                output.push_str("}\n");
+            }
+            PolicyFormat::Bwrap => {
+                // The pseudo filesystems are mounted instead of bound.
+                let is_below = |rule: &Rule, dir: &str| Path::new(&rule.path).starts_with(dir);
+
+                output.push_str("bwrap \\\n");
+                for rule in &self.rules {
+                    if is_below(rule, "/proc") || is_below(rule, "/dev") {
+                        continue;
+                    }
+
+                    let bind = match rule.access {
+                        Access::ReadOnly => "--ro-bind",
+                        Access::ReadWrite | Access::Create => "--bind",
+                    };
+                    let path = shell_quote(&rule.path);
+                    output.push_str(&format!("  {bind} {path} {path} \\\n"));
+                }
+                for (dir, arg) in [("/proc", "--proc"), ("/dev", "--dev")] {
+                    if self.rules.iter().any(|rule| is_below(rule, dir)) {
+                        output.push_str(&format!("  {arg} {dir} \\\n"));
+                    }
+                }
+                output.push_str(&format!("  {}\n", shell_quote(name)));
+            }
+        }
+
+        output
+    }
+}
+
+fn shell_quote(s: &str) -> String {
+    if !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || "/._-+:=,@".contains(c))
+    {
+        s.to_string()
+    } else {
+        format!("'{}'", s.replace('\'', r"'\''"))
+    }
+}
+
+fn apparmor_quote(path: &str) -> String {
+    if path.contains(char::is_whitespace) {
+        format!("\"{path}\"")
+    } else {
+        path.to_string()
+    }
+}
+
+impl Analysis {
+    /// Builds the sandbox policy from the files opened by all threads (see
+    /// [`Policy::build`]). Inherited fds don't need access to their path.
+    pub fn policy(&self, threshold: usize) -> Policy {
+        let sessions = self.threads().flat_map(|thread| {
+            thread
+                .files()
+                .values()
+                .flat_map(|info| &info.sessions)
+                .chain(thread.leaks())
+                .filter(|session| !session.is_inherited())
+        });
+
+        Policy::build(sessions, &self.executables(Path::new("/")), threshold)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{analysis::analyze, elf};
+
+    #[test]
+    fn test_policy() {
+        let mut trace = vec![
+            "Attaching 13 probes...".to_string(),
+            "1000;10;10;execve;/bin/app".into(),
+        ];
+        let opens = [
+            ("/etc/hosts", 524288),
+            ("/usr/lib/libc.so.6", 524288),
+            ("/usr/lib/libm.so.6", 524288),
+            ("/usr/lib/libz.so.1", 524288),
+            ("/usr/lib/gconv/gconv-modules", 524288),
+            ("/usr/lib/locale/locale-archive", 524288),
+            ("/var/log/app.log", 1025),
+            ("/etc/hosts", 2),
+            ("/tmp/out/a.o", 577),
+            ("/tmp/out/b.o", 577),
+            ("/proc/self/maps", 524288),
+            ("/srv/my data/input.txt", 0),
+            ("input.txt", 0),
+        ];
+        for (i, (path, flags)) in opens.into_iter().enumerate() {
+            let ts = 1100 + i * 100;
+            trace.push(format!("{ts};10;10;openat;4294967196;{path};{flags}"));
+            trace.push(format!("{};10;10;openat_exit;3", ts + 10));
+            trace.push(format!("{};10;10;close;3", ts + 20));
+            trace.push(format!("{};10;10;close_exit;0", ts + 30));
+        }
+        let analysis = analyze(&trace);
+
+        let policy = analysis.policy(3);
+        insta::assert_json_snapshot!(policy);
+
+        insta::assert_snapshot!(
+            "policy_landlock",
+            policy.render(PolicyFormat::Landlock, "app")
+        );
+        insta::assert_snapshot!(
+            "policy_apparmor",
+            policy.render(PolicyFormat::AppArmor, "app")
+        );
+        insta::assert_snapshot!("policy_bwrap", policy.render(PolicyFormat::Bwrap, "app"));
+
+        // Without collapsing, every file and the program get their own rule
+        assert_eq!(analysis.policy(0).rules.len(), 11);
+    }
+
+    #[test]
+    fn test_policy_exec() {
+        let exe = std::env::current_exe().unwrap();
+        let exe = exe.to_string_lossy();
+        let analysis = analyze(&[
+            "Attaching 13 probes...".to_string(),
+            format!("1000;10;10;execve;{exe}"),
+            "1100;10;10;openat;4294967196;/usr/lib/libc.so.6;524288".into(),
+            "1200;10;10;openat_exit;3".into(),
+            "1300;10;10;mmap;3;1000000;5;2066;163840".into(),
+            "1400;10;10;mmap_exit;140000000163840".into(),
+            "1500;10;10;close;3".into(),
+            "1600;10;10;close_exit;0".into(),
+        ]);
+        let policy = analysis.policy(0);
+
+        // The program, its interpreter and the mapped library
+        let interp = elf::interpreter(Path::new(exe.as_ref())).unwrap();
+        let rule = |path: &str| policy.rules.iter().find(|r| r.path == path).unwrap();
+        assert!(rule(&exe).exec && rule(&interp).exec);
+        assert!(rule("/usr/lib/libc.so.6").map && !rule("/usr/lib/libc.so.6").exec);
+
+        let apparmor = policy.render(PolicyFormat::AppArmor, "app");
+        assert!(apparmor.contains(&format!("  {exe} rix,\n")));
+        assert!(apparmor.contains(&format!("  {interp} rix,\n")));
+        assert!(apparmor.contains("  /usr/lib/libc.so.6 mr,\n"));
+
+        let bwrap = policy.render(PolicyFormat::Bwrap, "app");
+        assert!(bwrap.contains(&format!("--ro-bind {interp} {interp}")));
+    }
+}
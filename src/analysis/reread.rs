@@ -0,0 +1,323 @@
+use super::{
+    file::{FileEvent, FileSession},
+    utils, Analysis,
+};
+use crate::syscall::{
+    mmap_flags::{MAP_SHARED, PROT_WRITE},
+    open_flags::O_TRUNC,
+    pid_t, tid_t,
+};
+use itertools::Itertools;
+use std::collections::BTreeMap;
+
+/// The sessions that are compared with each other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(test, derive(serde::Serialize))]
+pub enum Scope {
+    Process(pid_t),
+    Thread(tid_t),
+}
+
+/// A file that was read in full multiple times without being modified in
+/// between.
+#[derive(Debug, PartialEq)]
+#[cfg_attr(test, derive(serde::Serialize))]
+pub struct ReRead {
+    pub scope: Scope,
+    pub path: String,
+    /// Number of sessions that read the file.
+    pub reads: usize,
+    /// Number of sessions that read the same content again.
+    pub redundant: usize,
+    pub wasted_bytes: usize,
+    /// Time spent in the syscalls of the redundant sessions (in ns).
+    pub wasted_time: u64,
+}
+
+/// Returns the bytes read by the session, or `None` if it didn't read the
+/// file in full or also wrote to it.
+///
+/// A file was read in full if a read hit the end of the file, either by
+/// returning 0 bytes or fewer bytes than requested.
+fn read_size(session: &FileSession) -> Option<usize> {
+    let mut size = 0;
+    let mut eof = session.eof;
+    for event in &session.events {
+        if event.is_write() {
+            return None;
+        }
+        if let FileEvent::Read {
+            requested, bytes, ..
+        } = event
+        {
+            eof |= bytes < requested;
+        }
+        size += event.bytes();
+    }
+    (size > 0 && eof).then_some(size)
+}
+
+/// Returns the timestamps at which the session modified the file: a truncating
+/// open, writes, truncates and allocations, and shared writable mappings.
+fn modifications(session: &FileSession) -> impl Iterator<Item = u64> + '_ {
+    let open = (session.flags & O_TRUNC != 0).then_some(session.open_ts);
+    let events = session
+        .events
+        .iter()
+        .filter(|e| {
+            e.is_write() || matches!(e, FileEvent::Truncate { .. } | FileEvent::Allocate { .. })
+        })
+        .map(|e| e.start_ts());
+    let mappings = session
+        .mappings
+        .iter()
+        .filter(|m| m.flags & MAP_SHARED != 0 && m.prot & PROT_WRITE != 0)
+        .map(|m| m.map_ts);
+    open.into_iter().chain(events).chain(mappings)
+}
+
+/// Returns the time spent in the syscalls of the session (in ns).
+fn syscall_time(session: &FileSession) -> u64 {
+    session.open_latency().unwrap_or_default()
+        + session.events.iter().map(|e| e.latency()).sum::<u64>()
+        + session.close_latency().unwrap_or_default()
+}
+
+/// Finds the sessions that read the same amount of bytes as the previous
+/// read, with no write to the file in between.
+///
+/// `sessions` are the candidates, `writes` the timestamps of all modifications
+/// of the file by any traced process.
+fn find_rereads(
+    scope: Scope, path: &str, sessions: &[&FileSession], writes: &[u64],
+) -> Option<ReRead> {
+    let mut reread = ReRead {
+        scope,
+        path: path.to_string(),
+        reads: 0,
+        redundant: 0,
+        wasted_bytes: 0,
+        wasted_time: 0,
+    };
+
+    let mut previous: Option<(u64, usize)> = None;
+    for session in sessions.iter().sorted_by_key(|s| s.open_ts) {
+        let Some(size) = read_size(session) else {
+            if !session.events.is_empty() {
+                previous = None;
+            }
+            continue;
+        };
+        reread.reads += 1;
+
+        let modified = |since: u64| writes.iter().any(|&ts| since < ts && ts < session.open_ts);
+        if let Some((since, previous_size)) = previous {
+            if previous_size == size && !modified(since) {
+                reread.redundant += 1;
+                reread.wasted_bytes += size;
+                reread.wasted_time += syscall_time(session);
+            }
+        }
+        previous = Some((session.open_ts, size));
+    }
+
+    (reread.redundant > 0).then_some(reread)
+}
+
+impl Analysis {
+    /// Returns the files that were read in full more than once, either within
+    /// each thread or across all threads of a process.
+    pub fn rereads(&self, per_thread: bool) -> Vec<ReRead> {
+        // Modifications by any thread or child process invalidate the previous
+        // reads, including those of sessions that were never closed.
+        let mut writes = BTreeMap::<&str, Vec<u64>>::new();
+        for thread in self.threads() {
+            for (path, info) in thread.files() {
+                let timestamps = info.sessions.iter().flat_map(modifications);
+                writes.entry(path).or_default().extend(timestamps);
+            }
+            for session in thread.leaks() {
+                let timestamps = modifications(session);
+                writes.entry(&session.path).or_default().extend(timestamps);
+            }
+        }
+
+        let mut groups = BTreeMap::<(Scope, &str), Vec<&FileSession>>::new();
+        for thread in self.threads() {
+            let scope = if per_thread {
+                Scope::Thread(thread.tid())
+            } else {
+                Scope::Process(thread.pid())
+            };
+            for (path, info) in thread.files() {
+                groups
+                    .entry((scope, path))
+                    .or_default()
+                    .extend(&info.sessions);
+            }
+        }
+
+        groups
+            .into_iter()
+            .filter_map(|((scope, path), sessions)| {
+                let writes = writes.get(path).map(Vec::as_slice).unwrap_or_default();
+                find_rereads(scope, path, &sessions, writes)
+            })
+            .sorted_by(|a, b| b.wasted_bytes.cmp(&a.wasted_bytes))
+            .collect()
+    }
+
+    pub fn print_rereads(&self, per_thread: bool) {
+        use termimad::print_inline as mdprintln;
+
+        let rereads = self.rereads(per_thread);
+        if rereads.is_empty() {
+            return;
+        }
+
+        let mut table = comfy_table::Table::new();
+        table.set_header(vec![
+            "Scope",
+            "Path",
+            "Reads",
+            "Redundant",
+            "Wasted bytes",
+            "Wasted time",
+        ]);
+        for reread in &rereads {
+            let scope = match reread.scope {
+                Scope::Process(pid) => format!("Process {pid}"),
+                Scope::Thread(tid) => format!("Thread {}", self.thread_label(tid)),
+            };
+            table.add_row(vec![
+                scope,
+                reread.path.clone(),
+                reread.reads.to_string(),
+                reread.redundant.to_string(),
+                reread.wasted_bytes.to_string(),
+                utils::format_ns(reread.wasted_time),
+            ]);
+        }
+
+        mdprintln(&format!(
+            "\n# **Redundant re-reads: {} files**\n\n",
+            rereads.len()
+        ));
+        println!("{table}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{analysis::analyze, tracer::BpfTracer};
+
+    #[test]
+    fn test_rereads_threaded() {
+        let raw_trace = include_str!("../../data/threaded.txt");
+        let analysis = Analysis::new(BpfTracer::parse_trace(raw_trace).unwrap());
+
+        insta::assert_json_snapshot!(analysis.rereads(false));
+        insta::assert_json_snapshot!(analysis.rereads(true));
+    }
+
+    #[test]
+    fn test_rereads_invalidated() {
+        let read = |ts: u64, path: &str, fd: u64, count: u64| {
+            [
+                format!("{ts};10;10;openat;4294967196;{path};524288"),
+                format!("{};10;10;openat_exit;{fd}", ts + 10),
+                format!("{};10;10;read;{fd};4096", ts + 20),
+                format!("{};10;10;read_exit;{count}", ts + 30),
+                format!("{};10;10;close;{fd}", ts + 40),
+                format!("{};10;10;close_exit;0", ts + 50),
+            ]
+        };
+        let trace = [
+            vec![
+                "Attaching 13 probes...".into(),
+                "1000;10;10;execve;/bin/app".into(),
+                "1010;10;10;fork;20".into(),
+            ],
+            // Not read in full, there might be more to read
+            read(1100, "/data/big.bin", 3, 4096).to_vec(),
+            read(1200, "/data/big.bin", 3, 4096).to_vec(),
+            read(1300, "/etc/app.conf", 3, 100).to_vec(),
+            // The child process modifies the file
+            vec![
+                "1400;20;20;openat;4294967196;/etc/app.conf;577".into(),
+                "1410;20;20;openat_exit;3".into(),
+                "1420;20;20;write;3;100".into(),
+                "1430;20;20;write_exit;100".into(),
+                "1440;20;20;close;3".into(),
+                "1450;20;20;close_exit;0".into(),
+            ],
+            read(1500, "/etc/app.conf", 3, 100).to_vec(),
+            read(1600, "/etc/app.conf", 3, 100).to_vec(),
+        ]
+        .concat();
+
+        let rereads = analyze(&trace).rereads(false);
+        assert_eq!(rereads.len(), 1);
+        assert_eq!(rereads[0].path, "/etc/app.conf");
+        assert_eq!((rereads[0].reads, rereads[0].redundant), (3, 1));
+    }
+
+    #[test]
+    fn test_rereads_truncated() {
+        let read = |ts: u64| {
+            [
+                format!("{ts};10;10;openat;4294967196;/etc/app.conf;524288"),
+                format!("{};10;10;openat_exit;3", ts + 10),
+                format!("{};10;10;read;3;4096", ts + 20),
+                format!("{};10;10;read_exit;100", ts + 30),
+                format!("{};10;10;close;3", ts + 40),
+                format!("{};10;10;close_exit;0", ts + 50),
+            ]
+        };
+        // The modifications are made by a child process, so that they don't
+        // interrupt the reads of the parent.
+        let modify = |ts: u64, flags: u64, calls: &[&str]| {
+            let mut lines = vec![
+                format!("{ts};20;20;openat;4294967196;/etc/app.conf;{flags}"),
+                format!("{};20;20;openat_exit;4", ts + 10),
+            ];
+            lines.extend(calls.iter().map(|call| format!("{};20;20;{call}", ts + 20)));
+            lines.push(format!("{};20;20;close;4", ts + 40));
+            lines.push(format!("{};20;20;close_exit;0", ts + 50));
+            lines
+        };
+        let trace = [
+            vec![
+                "Attaching 13 probes...".into(),
+                "1000;10;10;execve;/bin/app".into(),
+                "1010;10;10;fork;20".into(),
+            ],
+            read(1100).to_vec(),
+            // Emptied without a write
+            modify(1200, 514, &[]),
+            read(1300).to_vec(),
+            modify(1400, 2, &["ftruncate;4;0", "ftruncate_exit;0"]),
+            read(1500).to_vec(),
+            modify(1600, 2, &["fallocate;4;0;0;4096", "fallocate_exit;0"]),
+            read(1700).to_vec(),
+            // Written through a shared mapping
+            modify(1800, 2, &["mmap;4;4096;3;1;0", "mmap_exit;140000000000000"]),
+            read(1900).to_vec(),
+            // Written by a session that is never closed
+            vec![
+                "2000;20;20;openat;4294967196;/etc/app.conf;2".into(),
+                "2010;20;20;openat_exit;5".into(),
+                "2020;20;20;write;5;100".into(),
+                "2030;20;20;write_exit;100".into(),
+            ],
+            read(2100).to_vec(),
+            read(2200).to_vec(),
+        ]
+        .concat();
+
+        let rereads = analyze(&trace).rereads(false);
+        assert_eq!(rereads.len(), 1);
+        assert_eq!((rereads[0].reads, rereads[0].redundant), (7, 1));
+    }
+}
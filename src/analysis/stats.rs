@@ -0,0 +1,199 @@
+use super::{
+    file::{FileEvent, FileInfo},
+    utils,
+};
+
+/// Distribution of a set of sizes (in bytes).
+#[derive(Debug, Default, PartialEq)]
+#[cfg_attr(test, derive(serde::Serialize))]
+pub struct SizeStats {
+    pub count: usize,
+    pub sum: usize,
+    pub mean: f64,
+    pub min: usize,
+    pub max: usize,
+    pub stddev: f64,
+    pub p50: usize,
+    pub p90: usize,
+    pub p99: usize,
+}
+
+impl SizeStats {
+    pub fn new(sizes: &[usize]) -> Self {
+        if sizes.is_empty() {
+            return Self::default();
+        }
+
+        let mut sorted = sizes.iter().map(|&s| s as u64).collect::<Vec<_>>();
+        sorted.sort_unstable();
+
+        let count = sizes.len();
+        let sum = sizes.iter().sum::<usize>();
+        let mean = sum as f64 / count as f64;
+        let variance = sizes
+            .iter()
+            .map(|&s| (s as f64 - mean).powi(2))
+            .sum::<f64>()
+            / count as f64;
+
+        Self {
+            count,
+            sum,
+            mean,
+            min: sorted[0] as usize,
+            max: sorted[count - 1] as usize,
+            stddev: variance.sqrt(),
+            p50: utils::percentile(&sorted, 0.50) as usize,
+            p90: utils::percentile(&sorted, 0.90) as usize,
+            p99: utils::percentile(&sorted, 0.99) as usize,
+        }
+    }
+}
+
+/// Statistics of either the reads or the writes of a file.
+#[derive(Debug, Default, PartialEq)]
+#[cfg_attr(test, derive(serde::Serialize))]
+pub struct IoStats {
+    /// The sizes passed to the reads or writes (`count`).
+    pub requested: SizeStats,
+    /// The sizes returned by the syscalls, including the transfers.
+    pub returned: SizeStats,
+    /// Number of reads or writes that returned less than requested.
+    pub short: usize,
+}
+
+impl IoStats {
+    /// Creates the statistics from the `(requested, returned)` sizes of the
+    /// reads or writes, and the sizes of the transfers. Transfers usually
+    /// request far more than they copy (e.g. `cp` asks for 1 GiB), so their
+    /// requested size is ignored.
+    pub fn new(
+        sizes: impl IntoIterator<Item = (usize, usize)>, transfers: impl IntoIterator<Item = usize>,
+    ) -> Self {
+        let (requested, mut returned): (Vec<_>, Vec<_>) = sizes.into_iter().unzip();
+        let short = requested
+            .iter()
+            .zip(&returned)
+            .filter(|(requested, returned)| returned < requested)
+            .count();
+        returned.extend(transfers);
+
+        Self {
+            requested: SizeStats::new(&requested),
+            returned: SizeStats::new(&returned),
+            short,
+        }
+    }
+
+    /// Returns the fraction of reads or writes that returned less than
+    /// requested.
+    pub fn short_ratio(&self) -> f64 {
+        if self.requested.count == 0 {
+            return 0.0;
+        }
+        self.short as f64 / self.requested.count as f64
+    }
+}
+
+impl FileInfo {
+    /// Returns the statistics of the reads or writes, including the
+    /// transfers from or to the file.
+    fn io_stats(&self, read: bool) -> IoStats {
+        let events = self
+            .sessions
+            .iter()
+            .flat_map(|s| &s.events)
+            .filter(|event| {
+                if read {
+                    event.is_read()
+                } else {
+                    event.is_write()
+                }
+            });
+        let (transfers, syscalls): (Vec<_>, Vec<_>) =
+            events.partition(|event| matches!(event, FileEvent::Transfer { .. }));
+
+        IoStats::new(
+            syscalls.into_iter().filter_map(FileEvent::sizes),
+            transfers.into_iter().map(FileEvent::bytes),
+        )
+    }
+
+    pub fn read_stats(&self) -> IoStats {
+        self.io_stats(true)
+    }
+
+    pub fn write_stats(&self) -> IoStats {
+        self.io_stats(false)
+    }
+
+    /// Returns the table with the read and write statistics.
+    pub fn stats_table(&self) -> comfy_table::Table {
+        let (read, write) = (self.read_stats(), self.write_stats());
+
+        let mut table = comfy_table::Table::new();
+        table.set_header(vec!["", "Read", "Write"]);
+
+        let mut add_row = |name: &str, f: &dyn Fn(&IoStats) -> String| {
+            table.add_row(vec![name.to_string(), f(&read), f(&write)]);
+        };
+        add_row("Count", &|s| s.returned.count.to_string());
+        add_row("Total", &|s| s.returned.sum.to_string());
+        add_row("Average", &|s| format!("{:.2}", s.returned.mean));
+        add_row("Min", &|s| s.returned.min.to_string());
+        add_row("Max", &|s| s.returned.max.to_string());
+        add_row("Stddev", &|s| format!("{:.2}", s.returned.stddev));
+        add_row("p50 / p90 / p99", &|s| {
+            format!(
+                "{} / {} / {}",
+                s.returned.p50, s.returned.p90, s.returned.p99
+            )
+        });
+        add_row("Requested (avg)", &|s| format!("{:.2}", s.requested.mean));
+        add_row("Short", &|s| {
+            format!("{} ({:.1}%)", s.short, s.short_ratio() * 100.0)
+        });
+
+        table
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_size_stats() {
+        let stats = SizeStats::new(&[100, 200, 300, 400]);
+        assert_eq!(stats.count, 4);
+        assert_eq!(stats.sum, 1000);
+        assert_eq!(stats.mean, 250.0);
+        assert_eq!(stats.min, 100);
+        assert_eq!(stats.max, 400);
+        assert!((stats.stddev - 111.80).abs() < 0.01);
+        assert_eq!(stats.p50, 200);
+        assert_eq!(stats.p99, 400);
+
+        assert_eq!(SizeStats::new(&[]), SizeStats::default());
+    }
+
+    #[test]
+    fn test_io_stats() {
+        let stats = IoStats::new([(4096, 4096), (4096, 326), (8192, 8192)], []);
+        assert_eq!(stats.short, 1);
+        assert!((stats.short_ratio() - 1.0 / 3.0).abs() < f64::EPSILON);
+        assert_eq!(stats.requested.sum, 16384);
+        assert_eq!(stats.returned.sum, 12614);
+
+        // A read-only file still has a read average
+        let stats = IoStats::new([(1024, 326)], []);
+        assert_eq!(stats.returned.mean, 326.0);
+
+        // Transfers count as returned sizes, but are never short
+        let stats = IoStats::new([(4096, 4096), (4096, 326)], [65536, 1024]);
+        assert_eq!(stats.short, 1);
+        assert_eq!(stats.short_ratio(), 0.5);
+        assert_eq!(stats.requested.sum, 8192);
+        assert_eq!(stats.returned.count, 4);
+    }
+}
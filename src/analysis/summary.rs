@@ -0,0 +1,147 @@
+use super::{utils, Analysis};
+use std::{collections::BTreeMap, str::FromStr};
+
+/// The column the hot-file summary is ranked by (descending).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortBy {
+    Bytes,
+    Opens,
+    Duration,
+    Syscalls,
+}
+
+impl SortBy {
+    pub const VARIANTS: &'static [&'static str] = &["bytes", "opens", "duration", "syscalls"];
+}
+
+impl FromStr for SortBy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bytes" => Ok(SortBy::Bytes),
+            "opens" => Ok(SortBy::Opens),
+            "duration" => Ok(SortBy::Duration),
+            "syscalls" => Ok(SortBy::Syscalls),
+            _ => Err(anyhow::anyhow!("Unknown sort key: {s}")),
+        }
+    }
+}
+
+/// Statistics of a single path, aggregated across all threads.
+#[derive(Debug, Default, PartialEq)]
+#[cfg_attr(test, derive(serde::Serialize))]
+pub struct FileSummary {
+    pub path: String,
+    pub opens: usize,
+    pub read_bytes: usize,
+    pub write_bytes: usize,
+    /// The bytes of the file that were mapped (see
+    /// [`FileSession::mapped_bytes`](super::file::FileSession::mapped_bytes)).
+    pub mapped_bytes: u64,
+    /// Cumulative time the file was open (in ns).
+    pub duration: u64,
+    /// Number of read and write syscalls.
+    pub syscalls: usize,
+    pub threads: usize,
+}
+
+impl FileSummary {
+    pub const fn total_bytes(&self) -> usize {
+        self.read_bytes + self.write_bytes
+    }
+}
+
+impl Analysis {
+    /// Returns the statistics of all files, ranked by `sort_by`. Ties are
+    /// ordered by path, so that the output is deterministic.
+    pub fn file_summary(&self, sort_by: SortBy) -> Vec<FileSummary> {
+        let mut files = BTreeMap::<&str, FileSummary>::new();
+        for thread in self.threads() {
+            for (path, info) in thread.files() {
+                let (read_bytes, write_bytes) = info.total_bytes();
+
+                let summary = files.entry(path).or_insert_with(|| FileSummary {
+                    path: path.clone(),
+                    ..Default::default()
+                });
+                summary.opens += info.sessions.len();
+                summary.read_bytes += read_bytes;
+                summary.write_bytes += write_bytes;
+                summary.mapped_bytes += info.mapped_bytes();
+                summary.duration += info.total_duration();
+                summary.syscalls += info.sessions.iter().map(|s| s.events.len()).sum::<usize>();
+                summary.threads += 1;
+            }
+        }
+
+        let mut files = files.into_values().collect::<Vec<_>>();
+        files.sort_by(|a, b| {
+            let ordering = match sort_by {
+                SortBy::Bytes => a.total_bytes().cmp(&b.total_bytes()),
+                SortBy::Opens => a.opens.cmp(&b.opens),
+                SortBy::Duration => a.duration.cmp(&b.duration),
+                SortBy::Syscalls => a.syscalls.cmp(&b.syscalls),
+            };
+            ordering.reverse().then_with(|| a.path.cmp(&b.path))
+        });
+
+        files
+    }
+
+    /// Prints the `top` files ranked by `sort_by`.
+    pub fn print_summary(&self, top: usize, sort_by: SortBy) {
+        use termimad::print_inline as mdprintln;
+
+        let files = self.file_summary(sort_by);
+
+        let mut table = comfy_table::Table::new();
+        table.set_header(vec![
+            "Path",
+            "Opens",
+            "Read",
+            "Write",
+            "Mapped",
+            "Open duration",
+            "Syscalls",
+            "Threads",
+        ]);
+        for file in files.iter().take(top) {
+            table.add_row(vec![
+                file.path.clone(),
+                file.opens.to_string(),
+                file.read_bytes.to_string(),
+                file.write_bytes.to_string(),
+                file.mapped_bytes.to_string(),
+                format!("{:.2} ms", utils::ns_to_ms(file.duration as f64)),
+                file.syscalls.to_string(),
+                file.threads.to_string(),
+            ]);
+        }
+
+        mdprintln(&format!(
+            "\n# **Summary: top {} of {} files**\n\n",
+            top.min(files.len()),
+            files.len()
+        ));
+        println!("{table}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tracer::BpfTracer;
+
+    #[test]
+    fn test_summary_threaded() {
+        let raw_trace = include_str!("../../data/threaded.txt");
+        let analysis = Analysis::new(BpfTracer::parse_trace(raw_trace).unwrap());
+
+        insta::assert_json_snapshot!(analysis.file_summary(SortBy::Bytes));
+
+        let by_opens = analysis.file_summary(SortBy::Opens);
+        assert_eq!(by_opens[0].path, "/etc/hosts");
+        assert_eq!(by_opens[0].opens, 10);
+    }
+}
@@ -1,67 +1,200 @@
-use super::file::{FileInfo, FileSession};
+use super::{
+    file::{FileInfo, FileSession, Mapping, SessionKind, SyncOp},
+    metadata::{MetadataKind, MetadataOp},
+    utils,
+};
 use crate::{
-    analysis::{file::FileEvent, utils},
-    syscall::{fd_t, tid_t, RawSyscall, Syscall},
+    analysis::file::FileEvent,
+    fdinfo::FdInfo,
+    syscall::{
+        fd_t, mmap_flags::MAP_FIXED, open_flags::*, pid_t, socket::EINPROGRESS, tid_t, RawSyscall,
+        Syscall, AT_FDCWD,
+    },
 };
 use itertools::Itertools;
 use std::collections::HashMap;
 
+/// The `(ts, addr, len)` of an unmapped or synced memory range.
+pub(crate) type MemoryRange = (u64, u64, u64);
+
 #[derive(Debug)]
 #[cfg_attr(test, derive(serde::Serialize))]
 pub struct ThreadAnalysis {
+    pid: pid_t,
     tid: tid_t,
+    /// The names (`comm`) of the thread, in the order they were set.
+    #[cfg_attr(test, serde(skip_serializing_if = "Vec::is_empty"))]
+    names: Vec<String>,
+    /// The programs the thread executed (`execve`), or the program of the
+    /// attached process.
+    #[cfg_attr(test, serde(skip_serializing_if = "Vec::is_empty"))]
+    execs: Vec<String>,
     files: HashMap<String, FileInfo>,
+    /// The sessions of pipes and sockets, by label (see
+    /// [`SessionKind::label`]), kept apart from the files.
+    #[cfg_attr(test, serde(skip_serializing_if = "HashMap::is_empty"))]
+    channels: HashMap<String, FileInfo>,
+    /// The errors (negative errno) of the failed opens, by path.
+    #[cfg_attr(test, serde(skip_serializing_if = "HashMap::is_empty"))]
+    failed_opens: HashMap<String, Vec<i64>>,
+    /// The metadata syscalls and directory listings, by path.
+    #[cfg_attr(test, serde(skip_serializing_if = "HashMap::is_empty"))]
+    metadata: HashMap<String, Vec<MetadataOp>>,
+    /// Sessions that were still open at the end of the trace.
+    #[cfg_attr(test, serde(skip_serializing_if = "Vec::is_empty"))]
+    leaks: Vec<FileSession>,
+    /// The unmapped and synced memory ranges. The memory belongs to the
+    /// process, so they are matched with the mappings of all its threads
+    /// (see [`Self::resolve_mappings`]).
+    #[cfg_attr(test, serde(skip))]
+    unmaps: Vec<MemoryRange>,
+    #[cfg_attr(test, serde(skip))]
+    syncs: Vec<MemoryRange>,
 }
 
 impl ThreadAnalysis {
-    pub fn new(tid: tid_t, syscalls: &[Syscall]) -> Self {
+    /// Analyzes the syscalls of the thread. `inherited` are the fds that were
+    /// open when the trace started.
+    pub fn new(tid: tid_t, syscalls: &[Syscall], inherited: &HashMap<fd_t, FdInfo>) -> Self {
         log::info!("Thread {tid} got {} syscalls", syscalls.len());
 
+        let pid = syscalls.first().map(|s| s.pid).unwrap_or_default();
+        let start_ts = syscalls.first().map(|s| s.ts).unwrap_or_default();
+        let end_ts = syscalls.last().map(|s| s.ts).unwrap_or_default();
+
         let mut files = HashMap::new();
+        let mut channels = HashMap::new();
+        let mut failed_opens = HashMap::<String, Vec<i64>>::new();
+        let mut metadata = HashMap::<String, Vec<MetadataOp>>::new();
+        let mut execs = Vec::new();
 
         // All the current sessions. A new session is created when the file is opened,
         // and is removed from this list and added to `files` when the file is closed.
         //
-        let mut cur_sessions = {
-            let mut map = HashMap::new();
+        let mut cur_sessions = HashMap::<fd_t, FileSession>::new();
 
-            // Add the default sessions for stdin, stdout, and stderr
-            map.insert(0, FileSession::new("/dev/stdin"));
-            map.insert(1, FileSession::new("/dev/stdout"));
-            map.insert(2, FileSession::new("/dev/stderr"));
+        // Adds a session that ended to its file, or to its channel if it isn't a file.
+        let mut finish = |session: FileSession| {
+            let sessions = if session.kind.is_file() {
+                &mut files
+            } else {
+                &mut channels
+            };
+            sessions
+                .entry(session.path.clone())
+                .or_insert(FileInfo::default())
+                .sessions
+                .push(session);
+        };
 
-            map
+        // The inherited fds that weren't used yet. Their session is created when
+        // they are first used, and starts with the thread.
+        let mut inherited = inherited.clone();
+        let use_inherited = |sessions: &mut HashMap<fd_t, FileSession>,
+                             inherited: &mut HashMap<fd_t, FdInfo>,
+                             fd: fd_t| {
+            if let Some(info) = inherited.remove(&fd) {
+                sessions.insert(
+                    fd,
+                    FileSession {
+                        kind: SessionKind::from_path(&info.path),
+                        path: info.path,
+                        flags: info.flags,
+                        open_ts: start_ts,
+                        inherited: Some(fd),
+                        ..Default::default()
+                    },
+                );
+            }
+        };
+
+        // The unmapped and synced memory ranges. They are matched with the mappings
+        // once all threads are analyzed, when the sessions may have been closed
+        // already.
+        let mut unmaps = Vec::<MemoryRange>::new();
+        let mut syncs = Vec::<MemoryRange>::new();
+
+        // The recorded stack belongs to the syscall that directly follows it.
+        let mut pending_stack = None;
+
+        let mut names = Vec::<String>::new();
+        let mut rename = |name: &String| {
+            if names.last() != Some(name) {
+                names.push(name.clone());
+            }
         };
 
         let mut iter = syscalls.iter().multipeek();
         while let Some(call) = iter.next() {
             assert_eq!(tid, call.tid);
 
+            // `execve` reports the name of the program that is replaced.
+            if let Some(comm) = &call.comm {
+                if !matches!(call.raw, RawSyscall::Execve { .. }) {
+                    rename(comm);
+                }
+            }
+
+            let stack = match &call.raw {
+                RawSyscall::Stack { frames } => {
+                    pending_stack = Some(frames.clone());
+                    continue;
+                }
+                RawSyscall::SetName { name } => {
+                    rename(name);
+                    continue;
+                }
+                _ => pending_stack.take(),
+            };
+
             match &call.raw {
-                RawSyscall::OpenAt { path, .. } | RawSyscall::Open { path, .. } => {
-                    let Some(RawSyscall::OpenExit { ret } | RawSyscall::OpenAtExit { ret }) =
-                        iter.peek().map(|s| &s.raw)
+                RawSyscall::OpenAt { path, flags, .. } | RawSyscall::Open { path, flags, .. } => {
+                    let Some((
+                        end_ts,
+                        RawSyscall::OpenExit { ret } | RawSyscall::OpenAtExit { ret },
+                    )) = iter.peek().map(|s| (s.ts, &s.raw))
                     else {
                         log::warn!("Syscall not followed by exit: {call:?}");
                         continue;
                     };
 
-                    if *ret != -1 {
+                    let path = match &call.raw {
+                        RawSyscall::OpenAt { dirfd, .. } => {
+                            resolve_path(&cur_sessions, *dirfd, path)
+                        }
+                        _ => path.clone(),
+                    };
+
+                    if *ret >= 0 {
+                        inherited.remove(&(*ret as fd_t));
                         cur_sessions.insert(
                             *ret as fd_t,
                             FileSession {
                                 path: path.clone(),
+                                flags: *flags,
                                 open_ts: call.ts,
+                                open_end_ts: end_ts,
+                                stack,
                                 ..Default::default()
                             },
                         );
                         log::debug!("Created a new session for {path}");
+                    } else {
+                        failed_opens.entry(path).or_default().push(*ret);
                     }
                 }
 
-                RawSyscall::Read { fd, .. } | RawSyscall::Write { fd, .. } => {
+                RawSyscall::Read {
+                    fd,
+                    count: requested,
+                }
+                | RawSyscall::Write {
+                    fd,
+                    count: requested,
+                } => {
                     let is_read = matches!(call.raw, RawSyscall::Read { .. });
 
+                    use_inherited(&mut cur_sessions, &mut inherited, *fd);
                     let Some(cur_session) = cur_sessions.get_mut(fd) else {
                         log::warn!("RW without open: {call:?}");
                         continue;
@@ -94,73 +227,591 @@ impl ThreadAnalysis {
                     // - 0 = EOF
                     // - -1 = error
                     //
-                    if count > &0 {
+                    if is_read && *count == 0 {
+                        cur_session.eof = true;
+                    } else if count > &0 {
                         if is_read {
                             cur_session.events.push(FileEvent::Read {
+                                requested: *requested,
                                 bytes: *count as usize,
                                 start_ts: call.ts,
                                 end_ts,
+                                stack,
                             });
                         } else {
                             cur_session.events.push(FileEvent::Write {
+                                requested: *requested,
                                 bytes: *count as usize,
                                 start_ts: call.ts,
                                 end_ts,
+                                stack,
                             });
                         }
                     }
                 }
 
+                RawSyscall::Transfer {
+                    fd_in,
+                    fd_out,
+                    count: requested,
+                } => {
+                    let Some((end_ts, RawSyscall::TransferExit { count })) =
+                        iter.peek().map(|s| (s.ts, &s.raw))
+                    else {
+                        log::warn!("Syscall not followed by exit: {call:?}");
+                        continue;
+                    };
+                    if *count <= 0 {
+                        continue;
+                    }
+
+                    use_inherited(&mut cur_sessions, &mut inherited, *fd_in);
+                    use_inherited(&mut cur_sessions, &mut inherited, *fd_out);
+                    let path = |fd| cur_sessions.get(fd).map(|s: &FileSession| s.path.clone());
+                    let (source, destination) = (path(fd_in), path(fd_out));
+                    if source.is_none() || destination.is_none() {
+                        log::warn!("Transfer without open: {call:?}");
+                    }
+
+                    // The copy is recorded on both ends, with the path of the other one.
+                    for (fd, is_source, peer) in
+                        [(fd_in, true, destination), (fd_out, false, source)]
+                    {
+                        if let Some(session) = cur_sessions.get_mut(fd) {
+                            session.events.push(FileEvent::Transfer {
+                                requested: *requested,
+                                bytes: *count as usize,
+                                start_ts: call.ts,
+                                end_ts,
+                                stack: stack.clone(),
+                                is_source,
+                                peer: peer.unwrap_or_default(),
+                            });
+                        }
+                    }
+                }
+
+                RawSyscall::Mmap {
+                    fd,
+                    len,
+                    prot,
+                    flags,
+                    offset,
+                } => {
+                    let Some(RawSyscall::MmapExit { ret }) = iter.peek().map(|s| &s.raw) else {
+                        log::warn!("Syscall not followed by exit: {call:?}");
+                        continue;
+                    };
+                    if *ret < 0 {
+                        continue;
+                    }
+
+                    // A fixed mapping replaces the mappings in its range.
+                    let addr = *ret as u64;
+                    if flags & MAP_FIXED != 0 {
+                        unmaps.push((call.ts, addr, *len));
+                    }
+
+                    use_inherited(&mut cur_sessions, &mut inherited, *fd);
+                    let Some(session) = cur_sessions.get_mut(fd) else {
+                        log::warn!("Mmap without open: {call:?}");
+                        continue;
+                    };
+                    session.mappings.push(Mapping {
+                        addr,
+                        len: *len,
+                        prot: *prot,
+                        flags: *flags,
+                        offset: *offset,
+                        map_ts: call.ts,
+                        ..Default::default()
+                    });
+                }
+
+                RawSyscall::Munmap { addr, len } | RawSyscall::Msync { addr, len, .. } => {
+                    if let Some(
+                        RawSyscall::MunmapExit { ret: 0 } | RawSyscall::MsyncExit { ret: 0 },
+                    ) = iter.peek().map(|s| &s.raw)
+                    {
+                        let ranges = match call.raw {
+                            RawSyscall::Munmap { .. } => &mut unmaps,
+                            _ => &mut syncs,
+                        };
+                        ranges.push((call.ts, *addr, *len));
+                    }
+                }
+
+                RawSyscall::Fsync { fd }
+                | RawSyscall::Fdatasync { fd }
+                | RawSyscall::SyncFileRange { fd, .. }
+                | RawSyscall::Ftruncate { fd, .. }
+                | RawSyscall::Fallocate { fd, .. } => {
+                    let Some((
+                        end_ts,
+                        RawSyscall::SyncExit { ret }
+                        | RawSyscall::FtruncateExit { ret }
+                        | RawSyscall::FallocateExit { ret },
+                    )) = iter.peek().map(|s| (s.ts, &s.raw))
+                    else {
+                        log::warn!("Syscall not followed by exit: {call:?}");
+                        continue;
+                    };
+                    if *ret < 0 {
+                        continue;
+                    }
+
+                    let start_ts = call.ts;
+                    let event = match &call.raw {
+                        RawSyscall::Fsync { .. } => FileEvent::Sync {
+                            op: SyncOp::Fsync,
+                            start_ts,
+                            end_ts,
+                        },
+                        RawSyscall::Fdatasync { .. } => FileEvent::Sync {
+                            op: SyncOp::Fdatasync,
+                            start_ts,
+                            end_ts,
+                        },
+                        RawSyscall::SyncFileRange { .. } => FileEvent::Sync {
+                            op: SyncOp::SyncFileRange,
+                            start_ts,
+                            end_ts,
+                        },
+                        RawSyscall::Ftruncate { length, .. } => FileEvent::Truncate {
+                            length: *length,
+                            start_ts,
+                            end_ts,
+                        },
+                        RawSyscall::Fallocate { offset, len, .. } => FileEvent::Allocate {
+                            offset: *offset,
+                            len: *len,
+                            start_ts,
+                            end_ts,
+                        },
+                        _ => unreachable!(),
+                    };
+
+                    use_inherited(&mut cur_sessions, &mut inherited, *fd);
+                    let Some(session) = cur_sessions.get_mut(fd) else {
+                        log::warn!("Syscall without open: {call:?}");
+                        continue;
+                    };
+                    session.events.push(event);
+                }
+
+                RawSyscall::Stat { dirfd, path }
+                | RawSyscall::Access { dirfd, path }
+                | RawSyscall::Readlink { dirfd, path } => {
+                    let Some((end_ts, RawSyscall::MetadataExit { ret })) =
+                        iter.peek().map(|s| (s.ts, &s.raw))
+                    else {
+                        log::warn!("Syscall not followed by exit: {call:?}");
+                        continue;
+                    };
+
+                    let kind = match &call.raw {
+                        RawSyscall::Stat { .. } => MetadataKind::Stat,
+                        RawSyscall::Access { .. } => MetadataKind::Access,
+                        _ => MetadataKind::Readlink,
+                    };
+                    if *dirfd != AT_FDCWD {
+                        use_inherited(&mut cur_sessions, &mut inherited, *dirfd);
+                        // The dirfd is ignored for absolute paths
+                        if let Some(dir) = cur_sessions.get_mut(dirfd) {
+                            dir.used_as_dir |= !path.starts_with('/');
+                        }
+                    }
+                    metadata
+                        .entry(resolve_path(&cur_sessions, *dirfd, path))
+                        .or_default()
+                        .push(MetadataOp {
+                            kind,
+                            ret: *ret,
+                            start_ts: call.ts,
+                            end_ts,
+                        });
+                }
+
+                RawSyscall::Getdents { fd } => {
+                    let Some((end_ts, RawSyscall::GetdentsExit { ret })) =
+                        iter.peek().map(|s| (s.ts, &s.raw))
+                    else {
+                        log::warn!("Syscall not followed by exit: {call:?}");
+                        continue;
+                    };
+
+                    use_inherited(&mut cur_sessions, &mut inherited, *fd);
+                    let Some(dir) = cur_sessions.get_mut(fd) else {
+                        log::warn!("Getdents without open: {call:?}");
+                        continue;
+                    };
+                    dir.used_as_dir = true;
+                    metadata
+                        .entry(dir.path.clone())
+                        .or_default()
+                        .push(MetadataOp {
+                            kind: MetadataKind::Getdents,
+                            ret: *ret,
+                            start_ts: call.ts,
+                            end_ts,
+                        });
+                }
+
                 RawSyscall::Close { fd: close_fd } => {
+                    use_inherited(&mut cur_sessions, &mut inherited, *close_fd);
                     let Some(mut cur_session) = cur_sessions.remove(close_fd) else {
                         // FIXME: More syscalls need to be traced to also catch this.
                         log::warn!("Close without open: {call:?}");
                         continue;
                     };
                     cur_session.close_ts = call.ts;
+                    if let Some((end_ts, RawSyscall::CloseExit { .. })) =
+                        iter.peek().map(|s| (s.ts, &s.raw))
+                    {
+                        cur_session.close_end_ts = end_ts;
+                    }
                     log::debug!("Closed {}", cur_session.path);
 
-                    let file_info = files
-                        .entry(cur_session.path.clone())
-                        .or_insert(FileInfo::default());
-                    file_info.sessions.push(cur_session);
+                    finish(cur_session);
+                }
+
+                RawSyscall::Dup { fd } | RawSyscall::Dup2 { fd, .. } => {
+                    let Some((dup_end_ts, RawSyscall::DupExit { ret })) =
+                        iter.peek().map(|s| (s.ts, &s.raw))
+                    else {
+                        log::warn!("Syscall not followed by exit: {call:?}");
+                        continue;
+                    };
+
+                    // `dup2(fd, fd)` doesn't change anything.
+                    let newfd = *ret as fd_t;
+                    if *ret < 0 || newfd == *fd {
+                        continue;
+                    }
+
+                    use_inherited(&mut cur_sessions, &mut inherited, *fd);
+                    let Some(source) = cur_sessions.get(fd) else {
+                        log::warn!("Dup without open: {call:?}");
+                        continue;
+                    };
+                    let dup = FileSession {
+                        path: source.path.clone(),
+                        flags: source.flags,
+                        open_ts: call.ts,
+                        open_end_ts: dup_end_ts,
+                        stack,
+                        inherited: source.inherited,
+                        kind: source.kind,
+                        ..Default::default()
+                    };
+
+                    // `dup2` closes the new fd if it's open.
+                    inherited.remove(&newfd);
+                    if let Some(mut closed) = cur_sessions.insert(newfd, dup) {
+                        closed.close_ts = call.ts;
+                        finish(closed);
+                    }
+                }
+
+                RawSyscall::Pipe | RawSyscall::SocketPair { .. } => {
+                    let Some((end_ts, exit)) = iter.peek().map(|s| (s.ts, &s.raw)) else {
+                        log::warn!("Syscall not followed by exit: {call:?}");
+                        continue;
+                    };
+                    let (ret, fds, kind) = match (&call.raw, exit) {
+                        (
+                            RawSyscall::Pipe,
+                            RawSyscall::PipeExit {
+                                ret,
+                                read_fd,
+                                write_fd,
+                            },
+                        ) => (
+                            *ret,
+                            [(*read_fd, 0), (*write_fd, O_WRONLY)],
+                            SessionKind::Pipe,
+                        ),
+                        (
+                            RawSyscall::SocketPair {
+                                domain,
+                                socket_type,
+                            },
+                            RawSyscall::SocketPairExit { ret, fd, peer_fd },
+                        ) => (
+                            *ret,
+                            [(*fd, O_RDWR), (*peer_fd, O_RDWR)],
+                            SessionKind::from_socket(*domain, *socket_type),
+                        ),
+                        _ => {
+                            log::warn!("Syscall not followed by exit: {call:?}");
+                            continue;
+                        }
+                    };
+                    if ret < 0 {
+                        continue;
+                    }
+
+                    for (fd, flags) in fds {
+                        inherited.remove(&fd);
+                        cur_sessions.insert(
+                            fd,
+                            FileSession {
+                                path: kind.label(""),
+                                flags,
+                                open_ts: call.ts,
+                                open_end_ts: end_ts,
+                                stack: stack.clone(),
+                                kind,
+                                ..Default::default()
+                            },
+                        );
+                    }
+                }
+
+                RawSyscall::Socket {
+                    domain,
+                    socket_type,
+                } => {
+                    let Some((end_ts, RawSyscall::SocketExit { ret })) =
+                        iter.peek().map(|s| (s.ts, &s.raw))
+                    else {
+                        log::warn!("Syscall not followed by exit: {call:?}");
+                        continue;
+                    };
+                    if *ret < 0 {
+                        continue;
+                    }
+
+                    let kind = SessionKind::from_socket(*domain, *socket_type);
+                    inherited.remove(&(*ret as fd_t));
+                    cur_sessions.insert(
+                        *ret as fd_t,
+                        FileSession {
+                            path: kind.label(""),
+                            flags: O_RDWR,
+                            open_ts: call.ts,
+                            open_end_ts: end_ts,
+                            stack,
+                            kind,
+                            ..Default::default()
+                        },
+                    );
+                }
+
+                RawSyscall::Accept { fd } => {
+                    let Some((end_ts, RawSyscall::AcceptExit { ret })) =
+                        iter.peek().map(|s| (s.ts, &s.raw))
+                    else {
+                        log::warn!("Syscall not followed by exit: {call:?}");
+                        continue;
+                    };
+                    if *ret < 0 {
+                        continue;
+                    }
+
+                    // The connections are labeled with the address of the listening socket.
+                    use_inherited(&mut cur_sessions, &mut inherited, *fd);
+                    let (path, kind) = match cur_sessions.get(fd) {
+                        Some(listener) => (listener.path.clone(), listener.kind),
+                        None => {
+                            log::warn!("Accept without socket: {call:?}");
+                            (SessionKind::Socket.label(""), SessionKind::Socket)
+                        }
+                    };
+                    inherited.remove(&(*ret as fd_t));
+                    cur_sessions.insert(
+                        *ret as fd_t,
+                        FileSession {
+                            path,
+                            flags: O_RDWR,
+                            open_ts: call.ts,
+                            open_end_ts: end_ts,
+                            stack,
+                            kind,
+                            ..Default::default()
+                        },
+                    );
+                }
+
+                RawSyscall::Connect { fd, address } | RawSyscall::Bind { fd, address } => {
+                    let Some(RawSyscall::ConnectExit { ret } | RawSyscall::BindExit { ret }) =
+                        iter.peek().map(|s| &s.raw)
+                    else {
+                        log::warn!("Syscall not followed by exit: {call:?}");
+                        continue;
+                    };
+
+                    // Non-blocking sockets connect in the background (`EINPROGRESS`).
+                    if *ret < 0 && *ret != -EINPROGRESS {
+                        continue;
+                    }
+
+                    // A connected socket is labeled with the peer, otherwise with the local
+                    // address.
+                    use_inherited(&mut cur_sessions, &mut inherited, *fd);
+                    match cur_sessions.get_mut(fd) {
+                        Some(session) if !session.kind.is_file() => {
+                            session.path = session.kind.label(address);
+                        }
+                        _ => log::warn!("Connect or bind without socket: {call:?}"),
+                    }
+                }
+
+                RawSyscall::Execve { path } | RawSyscall::Attach { path } => {
+                    execs.push(path.clone());
                 }
 
                 _ => {}
             }
         }
 
-        // Close the stdin, stdout, and stderr sessions
-        //
-        #[cfg(feature = "trace-stdfd")]
-        for fd in 0..3 {
-            let mut cur_session = cur_sessions.remove(&fd).unwrap();
-            cur_session.open_ts = syscalls.first().unwrap().ts;
-            cur_session.close_ts = syscalls.last().unwrap().ts;
-
-            let file_info = files
-                .entry(cur_session.path.clone())
-                .or_insert(Default::default());
-            file_info.sessions.push(cur_session);
+        // The inherited fds stay open until the thread exits, so their sessions end
+        // with it. All others were leaked.
+        // The same goes for pipes and sockets, which are commonly held open.
+        let (open_inherited, leaks): (Vec<_>, Vec<_>) = cur_sessions
+            .into_iter()
+            .sorted_by_key(|(fd, _)| *fd)
+            .map(|(_, session)| session)
+            .partition(|session| session.is_inherited() || !session.kind.is_file());
+        for mut session in open_inherited {
+            session.close_ts = end_ts;
+            finish(session);
+        }
+
+        Self {
+            pid,
+            tid,
+            names,
+            execs,
+            files,
+            channels,
+            failed_opens,
+            metadata,
+            leaks,
+            unmaps,
+            syncs,
+        }
+    }
+
+    /// Returns the unmapped and synced memory ranges of the thread.
+    pub(crate) fn memory_ranges(&self) -> (&[MemoryRange], &[MemoryRange]) {
+        (&self.unmaps, &self.syncs)
+    }
+
+    /// Sets the end and the syncs of the mappings from the memory ranges of
+    /// all threads of the process. A mapping ends with the first `munmap` or
+    /// fixed `mmap` that overlaps its range, even partially, and is synced by
+    /// the `msync`s until then.
+    pub(crate) fn resolve_mappings(&mut self, unmaps: &[MemoryRange], syncs: &[MemoryRange]) {
+        let mappings = self
+            .files
+            .values_mut()
+            .chain(self.channels.values_mut())
+            .flat_map(|info| &mut info.sessions)
+            .chain(&mut self.leaks)
+            .flat_map(|session| &mut session.mappings);
+        for mapping in mappings {
+            let after = |(ts, addr, len): &&MemoryRange| {
+                *ts > mapping.map_ts && mapping.overlaps(*addr, *len)
+            };
+            let unmap_ts = unmaps
+                .iter()
+                .filter(after)
+                .map(|r| r.0)
+                .min()
+                .unwrap_or_default();
+            let synced = syncs
+                .iter()
+                .filter(after)
+                .filter(|(ts, ..)| unmap_ts == 0 || *ts < unmap_ts)
+                .count();
+            (mapping.unmap_ts, mapping.syncs) = (unmap_ts, synced);
+        }
+    }
+
+    pub const fn pid(&self) -> pid_t {
+        self.pid
+    }
+
+    pub const fn tid(&self) -> tid_t {
+        self.tid
+    }
+
+    /// Returns the last name of the thread, if it was traced.
+    pub fn name(&self) -> Option<&str> {
+        self.names.last().map(String::as_str)
+    }
+
+    /// Returns the thread id with its name (e.g. `1899 (tokio-runtime-w)`).
+    /// Renamed threads list all of their names.
+    pub fn label(&self) -> String {
+        match self.names.as_slice() {
+            [] => self.tid.to_string(),
+            names => format!("{} ({})", self.tid, names.join(" -> ")),
+        }
+    }
+
+    /// Returns the programs the thread executed.
+    pub fn execs(&self) -> &[String] {
+        &self.execs
+    }
+
+    pub fn files(&self) -> &HashMap<String, FileInfo> {
+        &self.files
+    }
+
+    /// Returns the sessions of pipes and sockets, by label.
+    pub fn channels(&self) -> &HashMap<String, FileInfo> {
+        &self.channels
+    }
+
+    pub fn failed_opens(&self) -> &HashMap<String, Vec<i64>> {
+        &self.failed_opens
+    }
+
+    /// Returns the metadata syscalls and directory listings, by path.
+    pub fn metadata(&self) -> &HashMap<String, Vec<MetadataOp>> {
+        &self.metadata
+    }
+
+    pub fn leaks(&self) -> &[FileSession] {
+        &self.leaks
+    }
+
+    /// Removes the sessions of inherited fds (and their duplicates).
+    pub fn remove_inherited(&mut self) {
+        for sessions in [&mut self.files, &mut self.channels] {
+            for info in sessions.values_mut() {
+                info.sessions.retain(|session| !session.is_inherited());
+            }
+            sessions.retain(|_, info| !info.sessions.is_empty());
         }
+    }
 
-        Self { tid, files }
+    pub fn retain_files(&mut self, mut f: impl FnMut(&str) -> bool) {
+        self.files.retain(|path, _| f(path));
+        self.channels.retain(|label, _| f(label));
+        self.failed_opens.retain(|path, _| f(path));
+        self.metadata.retain(|path, _| f(path));
+        self.leaks.retain(|session| f(&session.path));
     }
 
     pub fn print_result(&self) {
         use termimad::print_inline as mdprintln;
 
-        mdprintln(&format!("\n# **Thread: {}**\n\n", self.tid));
-        for (path, file_info) in &self.files {
+        mdprintln(&format!("\n# **Thread {}**\n\n", self.label()));
+        for (path, file_info) in self.files.iter().sorted_by_key(|(path, _)| *path) {
             mdprintln(&format!("\n## File: **{}**\n\n", path));
             println!("Opened: {} times", file_info.sessions.len());
 
-            let total_duration = utils::ns_to_ms(
-                file_info.sessions.iter().map(|s| s.duration()).sum::<u64>() as f64,
-            );
-            let avg_duration = total_duration / file_info.sessions.len() as f64;
-            mdprintln(&format!("Total duration: {:.2} ms\n", total_duration));
-            mdprintln(&format!("Avg session duration: {:.2} ms\n", avg_duration));
+            mdprintln(&format!(
+                "Total duration: {:.2} ms\n",
+                file_info.total_duration_ms()
+            ));
+            mdprintln(&format!(
+                "Avg session duration: {:.2} ms\n",
+                file_info.avg_duration_ms()
+            ));
 
             // Sessions
             //
@@ -173,6 +824,14 @@ impl ThreadAnalysis {
                     session.idle_time_ms()
                 ));
 
+                for mapping in &session.mappings {
+                    mdprintln(&format!(
+                        "- **Mapping**: {} bytes at offset {} ({})\n",
+                        mapping.len,
+                        mapping.offset,
+                        mapping.format()
+                    ));
+                }
                 for (j, event) in session.events.iter().enumerate() {
                     match event {
                         FileEvent::Read { bytes, .. } => {
@@ -181,34 +840,70 @@ impl ThreadAnalysis {
                         FileEvent::Write { bytes, .. } => {
                             mdprintln(&format!("- **Event {}**: Write {} bytes\n", j + 1, bytes));
                         }
+                        FileEvent::Transfer {
+                            bytes,
+                            is_source,
+                            peer,
+                            ..
+                        } => {
+                            let direction = if *is_source { "to" } else { "from" };
+                            mdprintln(&format!(
+                                "- **Event {}**: Copy {} bytes {direction} {peer}\n",
+                                j + 1,
+                                bytes
+                            ));
+                        }
+                        FileEvent::Sync { op, .. } => {
+                            mdprintln(&format!(
+                                "- **Event {}**: {} in {:.2} ms\n",
+                                j + 1,
+                                op.name(),
+                                utils::ns_to_ms(event.latency() as f64)
+                            ));
+                        }
+                        FileEvent::Truncate { length, .. } => {
+                            mdprintln(&format!(
+                                "- **Event {}**: Truncate to {} bytes\n",
+                                j + 1,
+                                length
+                            ));
+                        }
+                        FileEvent::Allocate { offset, len, .. } => {
+                            mdprintln(&format!(
+                                "- **Event {}**: Allocate {} bytes at offset {}\n",
+                                j + 1,
+                                len,
+                                offset
+                            ));
+                        }
                     }
                 }
             }
 
+            if file_info.mapped_bytes() > 0 {
+                mdprintln(&format!("Mapped: {} bytes\n", file_info.mapped_bytes()));
+            }
+
             // IO Statistics
             //
-            let (total_read, total_write) = file_info.total_bytes();
-            let (avg_read, avg_write) = file_info.avg_size().unwrap_or_default();
-            let (max_read, max_write) = file_info.max_size();
-
-            let mut io_table = comfy_table::Table::new();
-            io_table
-                .set_header(vec!["", "Read", "Write"])
-                .add_row(vec![
-                    "Total",
-                    &total_read.to_string(),
-                    &total_write.to_string(),
-                ])
-                .add_row(vec![
-                    "Average",
-                    &avg_read.to_string(),
-                    &avg_write.to_string(),
-                ])
-                .add_row(vec!["Max", &max_read.to_string(), &max_write.to_string()]);
-
             // mdprintln("\n### **IO Statistics**:\n\n");
             println!();
-            println!("{io_table}");
+            println!("{}", file_info.stats_table());
         }
     }
 }
+
+/// Resolves a path relative to a directory fd with the path of its session, and
+/// an empty path (`AT_EMPTY_PATH`) to the path of the fd itself. Paths relative
+/// to the working directory are kept as they are.
+fn resolve_path(sessions: &HashMap<fd_t, FileSession>, dirfd: fd_t, path: &str) -> String {
+    if dirfd == AT_FDCWD || path.starts_with('/') {
+        return path.to_string();
+    }
+
+    match sessions.get(&dirfd) {
+        Some(dir) if path.is_empty() => dir.path.clone(),
+        Some(dir) => format!("{}/{path}", dir.path.trim_end_matches('/')),
+        None => path.to_string(),
+    }
+}
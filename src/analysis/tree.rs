@@ -0,0 +1,189 @@
+use super::{
+    summary::{FileSummary, SortBy},
+    Analysis,
+};
+use itertools::Itertools;
+use std::{collections::BTreeMap, str::FromStr};
+
+/// The statistic that is used to rank directories and weight folded stacks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Weight {
+    Bytes,
+    Opens,
+}
+
+impl Weight {
+    pub const VARIANTS: &'static [&'static str] = &["bytes", "opens"];
+}
+
+impl FromStr for Weight {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bytes" => Ok(Weight::Bytes),
+            "opens" => Ok(Weight::Opens),
+            _ => Err(anyhow::anyhow!("Unknown weight: {s}")),
+        }
+    }
+}
+
+/// A directory (or file) with the statistics of all files below it.
+#[derive(Debug, Default)]
+pub struct DirNode {
+    pub bytes: usize,
+    pub opens: usize,
+    pub files: usize,
+    pub children: BTreeMap<String, DirNode>,
+}
+
+impl DirNode {
+    /// Builds the tree from the files. Paths deeper than `depth` components
+    /// are rolled up into their ancestor at that depth.
+    pub fn build(files: &[FileSummary], depth: Option<usize>) -> Self {
+        let mut root = DirNode::default();
+        for file in files {
+            // Relative paths (e.g. `openat` with a dirfd) are grouped below `.`
+            let components = match file.path.strip_prefix('/') {
+                Some(path) => path.split('/').collect::<Vec<_>>(),
+                None => std::iter::once(".").chain(file.path.split('/')).collect(),
+            };
+
+            let mut node = &mut root;
+            node.add(file);
+            for component in components
+                .into_iter()
+                .filter(|c| !c.is_empty())
+                .take(depth.unwrap_or(usize::MAX))
+            {
+                node = node.children.entry(component.to_string()).or_default();
+                node.add(file);
+            }
+        }
+
+        root
+    }
+
+    fn add(&mut self, file: &FileSummary) {
+        self.bytes += file.total_bytes();
+        self.opens += file.opens;
+        self.files += 1;
+    }
+
+    pub const fn weight(&self, weight: Weight) -> usize {
+        match weight {
+            Weight::Bytes => self.bytes,
+            Weight::Opens => self.opens,
+        }
+    }
+
+    /// Returns the children with the heaviest first.
+    fn sorted_children(&self, weight: Weight) -> impl Iterator<Item = (&String, &DirNode)> {
+        self.children.iter().sorted_by(|(a_name, a), (b_name, b)| {
+            b.weight(weight)
+                .cmp(&a.weight(weight))
+                .then_with(|| a_name.cmp(b_name))
+        })
+    }
+
+    /// Returns the tree in the folded stack format (`etc;passwd 4366`) that
+    /// is used by flamegraph tools. Every node is emitted with its own weight,
+    /// i.e. the part of its weight that is not accounted for by its children
+    /// (e.g. a directory that was opened itself).
+    pub fn folded(&self, weight: Weight) -> String {
+        fn visit(node: &DirNode, stack: &mut Vec<String>, weight: Weight, output: &mut String) {
+            let children = node
+                .children
+                .values()
+                .map(|child| child.weight(weight))
+                .sum::<usize>();
+            let own = node.weight(weight).saturating_sub(children);
+            if own > 0 && !stack.is_empty() {
+                output.push_str(&format!("{} {own}\n", stack.join(";")));
+            }
+
+            for (name, child) in &node.children {
+                stack.push(name.replace(';', "_"));
+                visit(child, stack, weight, output);
+                stack.pop();
+            }
+        }
+
+        let mut output = String::new();
+        visit(self, &mut Vec::new(), weight, &mut output);
+        output
+    }
+
+    /// Returns the tree as a table with one indented row per node.
+    pub fn table(&self, weight: Weight) -> comfy_table::Table {
+        fn visit(
+            node: &DirNode, name: &str, level: usize, weight: Weight,
+            table: &mut comfy_table::Table,
+        ) {
+            let suffix = if node.children.is_empty() { "" } else { "/" };
+            table.add_row(vec![
+                format!("{}{name}{suffix}", "  ".repeat(level)),
+                node.bytes.to_string(),
+                node.opens.to_string(),
+                node.files.to_string(),
+            ]);
+
+            for (name, child) in node.sorted_children(weight) {
+                visit(child, name, level + 1, weight, table);
+            }
+        }
+
+        let mut table = comfy_table::Table::new();
+        table.set_header(vec!["Path", "Bytes", "Opens", "Files"]);
+        for (name, child) in self.sorted_children(weight) {
+            visit(child, &format!("/{name}"), 0, weight, &mut table);
+        }
+
+        table
+    }
+}
+
+impl Analysis {
+    /// Aggregates the files of all threads up the directory tree.
+    pub fn dir_tree(&self, depth: Option<usize>) -> DirNode {
+        DirNode::build(&self.file_summary(SortBy::Bytes), depth)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tracer::BpfTracer;
+
+    #[test]
+    fn test_tree_threaded() {
+        let raw_trace = include_str!("../../data/threaded.txt");
+        let analysis = Analysis::new(BpfTracer::parse_trace(raw_trace).unwrap());
+
+        let tree = analysis.dir_tree(None);
+        assert_eq!(tree.files, 5);
+        assert_eq!(tree.children["etc"].opens, 13);
+        insta::assert_snapshot!(tree.folded(Weight::Bytes));
+
+        let tree = analysis.dir_tree(Some(1));
+        insta::assert_snapshot!(tree.folded(Weight::Opens), @r"
+        etc 13
+        nix 2
+        proc 1
+        ");
+    }
+
+    #[test]
+    fn test_folded_own_weight() {
+        let file = |path: &str, opens| FileSummary {
+            path: path.to_string(),
+            opens,
+            ..Default::default()
+        };
+        let tree = DirNode::build(&[file("/etc", 2), file("/etc/passwd", 3)], None);
+        insta::assert_snapshot!(tree.folded(Weight::Opens), @r"
+        etc 2
+        etc;passwd 3
+        ");
+    }
+}
@@ -1,3 +1,48 @@
+use std::path::{Component, Path, PathBuf};
+
+/// Pseudo filesystems, whose files are provided by the kernel.
+pub const PSEUDO_FILESYSTEMS: &[&str] = &["/proc", "/sys", "/dev"];
+
 pub const fn ns_to_ms(ns: f64) -> f64 {
     ns / 1_000_000.0
 }
+
+/// Formats the duration with a unit that fits its magnitude (e.g. `12.34 µs`).
+pub fn format_ns(ns: u64) -> String {
+    match ns {
+        0..1_000 => format!("{ns} ns"),
+        1_000..1_000_000 => format!("{:.2} µs", ns as f64 / 1_000.0),
+        1_000_000..1_000_000_000 => format!("{:.2} ms", ns_to_ms(ns as f64)),
+        _ => format!("{:.2} s", ns as f64 / 1_000_000_000.0),
+    }
+}
+
+/// Returns the nearest-rank percentile (`0.0..=1.0`) of the sorted values.
+pub fn percentile(sorted: &[u64], p: f64) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+
+    let rank = (p * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+pub fn is_pseudo_fs(path: &Path) -> bool {
+    PSEUDO_FILESYSTEMS.iter().any(|fs| path.starts_with(fs))
+}
+
+/// Removes the `.` and `..` components of the path, without resolving
+/// symlinks.
+pub fn clean_path(path: &Path) -> PathBuf {
+    let mut cleaned = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                cleaned.pop();
+            }
+            component => cleaned.push(component),
+        }
+    }
+    cleaned
+}
@@ -0,0 +1,79 @@
+use std::{io::Read, path::Path};
+
+/// Type of the program header that holds the path of the interpreter.
+const PT_INTERP: u32 = 3;
+
+/// The program headers and the interpreter are at the start of the file, so
+/// there's no need to read large binaries completely.
+const HEADER_SIZE: u64 = 64 * 1024;
+
+/// Returns the interpreter (`PT_INTERP`) of the ELF file, e.g.
+/// `/lib64/ld-linux-x86-64.so.2`. `None` if the file can't be read, isn't an
+/// ELF file or is statically linked.
+pub fn interpreter(path: &Path) -> Option<String> {
+    let mut data = Vec::new();
+    std::fs::File::open(path)
+        .ok()?
+        .take(HEADER_SIZE)
+        .read_to_end(&mut data)
+        .ok()?;
+    if data.get(..4)? != b"\x7fELF" {
+        return None;
+    }
+
+    let is_64 = *data.get(4)? == 2;
+    let is_le = *data.get(5)? == 1;
+    let int = |offset: usize, size: usize| -> Option<u64> {
+        let bytes = data.get(offset..offset.checked_add(size)?)?;
+        let mut buf = [0u8; 8];
+        if is_le {
+            buf[..size].copy_from_slice(bytes);
+            Some(u64::from_le_bytes(buf))
+        } else {
+            buf[8 - size..].copy_from_slice(bytes);
+            Some(u64::from_be_bytes(buf))
+        }
+    };
+
+    // The offsets of the fields in the ELF and program headers differ
+    // between 32 and 64 bit.
+    let (phoff, phentsize, phnum) = if is_64 {
+        (int(0x20, 8)?, int(0x36, 2)?, int(0x38, 2)?)
+    } else {
+        (int(0x1c, 4)?, int(0x2a, 2)?, int(0x2c, 2)?)
+    };
+    let (offset_field, filesz_field, size) = if is_64 {
+        (0x08, 0x20, 8)
+    } else {
+        (0x04, 0x10, 4)
+    };
+
+    (0..phnum).find_map(|i| {
+        let header = usize::try_from(i.checked_mul(phentsize)?.checked_add(phoff)?).ok()?;
+        if int(header, 4)? != PT_INTERP as u64 {
+            return None;
+        }
+
+        let offset = usize::try_from(int(header.checked_add(offset_field)?, size)?).ok()?;
+        let len = usize::try_from(int(header.checked_add(filesz_field)?, size)?).ok()?;
+        let interp = data.get(offset..offset.checked_add(len)?)?;
+        let interp = interp.split(|&b| b == 0).next()?;
+        Some(String::from_utf8_lossy(interp).to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interpreter() {
+        // The test binary is dynamically linked
+        let exe = std::env::current_exe().unwrap();
+        let interp = interpreter(&exe).unwrap();
+        assert!(interp.starts_with('/') && interp.contains("ld"), "{interp}");
+
+        assert_eq!(interpreter(Path::new("Cargo.toml")), None);
+        assert_eq!(interpreter(Path::new("/does/not/exist")), None);
+    }
+}
@@ -0,0 +1,82 @@
+use crate::syscall::{fd_t, open_flags::O_CLOEXEC, pid_t};
+use itertools::Itertools;
+
+/// An open fd of a process, from `/proc/<pid>/fd` and `/proc/<pid>/fdinfo`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(test, derive(serde::Serialize))]
+pub struct FdInfo {
+    pub fd: fd_t,
+    /// The target of the link in `/proc/<pid>/fd`, e.g. a path or
+    /// `pipe:[1234]`.
+    pub path: String,
+    /// The flags of the file, as passed to `open`.
+    pub flags: u64,
+    /// The file offset.
+    pub pos: u64,
+}
+
+impl FdInfo {
+    /// Parses the content of `/proc/<pid>/fdinfo/<fd>`.
+    pub fn parse(fd: fd_t, path: String, fdinfo: &str) -> Option<Self> {
+        let field = |name: &str| {
+            fdinfo
+                .lines()
+                .find_map(|line| line.strip_prefix(name))
+                .map(str::trim)
+        };
+
+        Some(Self {
+            fd,
+            path,
+            flags: u64::from_str_radix(field("flags:")?, 8).ok()?,
+            pos: field("pos:")?.parse().ok()?,
+        })
+    }
+
+    /// Returns the record of the fd in the trace, without the timestamp, pid
+    /// and tid.
+    pub fn record(&self) -> String {
+        format!(
+            "inherited_fd;{};{};{};{}",
+            self.fd, self.flags, self.pos, self.path
+        )
+    }
+}
+
+/// Returns the open fds of the process, ordered by fd. With `exec`, only the
+/// fds that are kept across `execve` (without `O_CLOEXEC`) are returned.
+pub fn snapshot(pid: pid_t, exec: bool) -> Vec<FdInfo> {
+    let Ok(entries) = std::fs::read_dir(format!("/proc/{pid}/fd")) else {
+        log::warn!("Failed to read the fds of {pid}");
+        return Vec::new();
+    };
+
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let fd = entry.file_name().to_str()?.parse::<fd_t>().ok()?;
+            let path = std::fs::read_link(entry.path()).ok()?;
+            let fdinfo = std::fs::read_to_string(format!("/proc/{pid}/fdinfo/{fd}")).ok()?;
+            FdInfo::parse(fd, path.to_string_lossy().to_string(), &fdinfo)
+        })
+        .filter(|info| !exec || info.flags & O_CLOEXEC == 0)
+        .sorted_by_key(|info| info.fd)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_fdinfo() {
+        let fdinfo = "pos:\t4096\nflags:\t02102001\nmnt_id:\t29\nino:\t1234\n";
+        let info = FdInfo::parse(3, "/var/log/app.log".into(), fdinfo).unwrap();
+        assert_eq!(info.pos, 4096);
+        assert_eq!(info.flags, 0o2102001);
+        assert_eq!(info.record(), "inherited_fd;3;558081;4096;/var/log/app.log");
+
+        // The fds of the test itself
+        assert!(!snapshot(std::process::id() as pid_t, false).is_empty());
+    }
+}
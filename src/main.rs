@@ -1,42 +1,517 @@
-use crate::tracer::BpfTracer;
-use analysis::Analysis;
+use crate::{
+    syscall::pid_t,
+    tracer::{BpfTracer, Stacks, TraceOptions},
+};
+use analysis::{
+    budget::Budget, deps::DepsFormat, filter::PathFilter, idle::IdleConfig,
+    inefficient::SmallIoConfig, manifest::ManifestFormat, policy::PolicyFormat, summary::SortBy,
+    tree::Weight, Analysis,
+};
+use replay::{ReplayFormat, Workload};
 use std::path::PathBuf;
-use structopt::StructOpt;
+use structopt::{clap::AppSettings, StructOpt};
 
 #[derive(Debug, StructOpt)]
-#[structopt(name = "fdtrace", about = "File syscall tracer")]
+#[structopt(
+    name = "fdtrace",
+    about = "File syscall tracer",
+    setting = AppSettings::SubcommandsNegateReqs,
+    setting = AppSettings::ArgsNegateSubcommands
+)]
 pub struct Opt {
     /// Activate debug mode
     // short and long flags (-d, --debug) will be deduced from the field's name
     #[structopt(short, long)]
     pub debug: bool,
 
+    #[structopt(subcommand)]
+    pub cmd: Option<Command>,
+
+    #[structopt(flatten)]
+    pub source: Source,
+
+    #[structopt(flatten)]
+    pub report: ReportOpt,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct ReportOpt {
+    /// Number of files in the summary
+    #[structopt(long, default_value = "10")]
+    pub top: usize,
+
+    /// Rank the files in the summary by this column
+    #[structopt(long, default_value = "bytes", possible_values = SortBy::VARIANTS)]
+    pub sort_by: SortBy,
+
+    /// Reads and writes below this size (in bytes) are reported as inefficient
+    #[structopt(long, default_value = "512")]
+    pub small_io_threshold: usize,
+
+    /// Minimum number of small reads or writes in a session to report it
+    #[structopt(long, default_value = "16")]
+    pub small_io_min_calls: usize,
+
+    /// Buffer size (in KiB) used to estimate the syscalls saved by buffering
+    #[structopt(long, default_value = "8")]
+    pub buffer_size: usize,
+
+    /// Report redundant re-reads within each thread instead of each process
+    #[structopt(long)]
+    pub rereads_per_thread: bool,
+
+    /// Sessions that are idle for more than this fraction of their duration
+    /// are reported
+    #[structopt(long, default_value = "0.9")]
+    pub idle_ratio: f64,
+
+    /// Minimum duration (in ms) of an idle session to report it
+    #[structopt(long, default_value = "100")]
+    pub idle_min_duration: u64,
+
+    /// Print the latency distribution of the syscalls per thread and file
+    #[structopt(long)]
+    pub latency: bool,
+}
+
+impl ReportOpt {
+    pub fn small_io_config(&self) -> SmallIoConfig {
+        SmallIoConfig {
+            threshold: self.small_io_threshold,
+            min_calls: self.small_io_min_calls,
+            buffer_size: self.buffer_size * 1024,
+        }
+    }
+
+    pub fn idle_config(&self) -> IdleConfig {
+        IdleConfig {
+            ratio: self.idle_ratio,
+            min_duration: self.idle_min_duration * 1_000_000,
+        }
+    }
+}
+
+#[derive(Debug, StructOpt)]
+pub enum Command {
+    /// Browse the analysis in an interactive terminal UI
+    Tui {
+        #[structopt(flatten)]
+        source: Source,
+    },
+
+    /// Aggregate the I/O up the directory tree
+    Tree {
+        #[structopt(flatten)]
+        source: Source,
+
+        /// Maximum number of path components, deeper paths are rolled up
+        #[structopt(long)]
+        depth: Option<usize>,
+
+        /// Rank directories and weight the folded stacks by this statistic
+        #[structopt(long, default_value = "bytes", possible_values = Weight::VARIANTS)]
+        weight: Weight,
+
+        /// Print folded stacks for flamegraph tools instead of a tree
+        #[structopt(long)]
+        folded: bool,
+    },
+
+    /// Check the I/O of the program against a budget, fails if a limit is
+    /// exceeded
+    Check {
+        #[structopt(flatten)]
+        source: Source,
+
+        /// TOML file with the limits (`max_files`, `max_read_bytes`,
+        /// `max_write_bytes`, `forbidden` and `[max_opens]`)
+        #[structopt(long, parse(from_os_str))]
+        budget: PathBuf,
+    },
+
+    /// Generate a filesystem sandbox policy from the accessed paths
+    Policy {
+        #[structopt(flatten)]
+        source: Source,
+
+        #[structopt(long, default_value = "bwrap", possible_values = PolicyFormat::VARIANTS)]
+        format: PolicyFormat,
+
+        /// Collapse the paths of a directory into a directory rule if at least
+        /// this many need the same access (0 to disable)
+        #[structopt(long, default_value = "5")]
+        threshold: usize,
+
+        /// Name of the AppArmor profile, or the program run by bwrap.
+        /// Defaults to the traced program
+        #[structopt(long)]
+        name: Option<String>,
+    },
+
+    /// List the input and output files of a command (e.g. a compiler) and
+    /// its child processes
+    Deps {
+        #[structopt(flatten)]
+        source: Source,
+
+        #[structopt(long, default_value = "make", possible_values = DepsFormat::VARIANTS)]
+        format: DepsFormat,
+
+        /// Target of the Make rule, defaults to the outputs
+        #[structopt(long)]
+        target: Option<String>,
+
+        /// Working directory of the command, used to resolve relative paths.
+        /// Defaults to the current directory
+        #[structopt(long, parse(from_os_str))]
+        cwd: Option<PathBuf>,
+
+        /// Write the dependencies to this file instead of stdout
+        #[structopt(short, long, parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+
+    /// List the files, symlinks and directories needed to run the program in
+    /// a minimal image
+    Manifest {
+        #[structopt(flatten)]
+        source: Source,
+
+        #[structopt(long, default_value = "tar", possible_values = ManifestFormat::VARIANTS)]
+        format: ManifestFormat,
+
+        /// Root of the filesystem the paths are resolved in (e.g. an unpacked
+        /// image)
+        #[structopt(long, default_value = "/", parse(from_os_str))]
+        root: PathBuf,
+
+        /// Build stage or image the `COPY` instructions copy from
+        #[structopt(long, default_value = "rootfs")]
+        from: String,
+
+        /// Write the manifest to this file instead of stdout
+        #[structopt(short, long, parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+
+    /// Replay the file I/O of a recorded trace in a scratch directory
+    Replay {
+        /// The recorded trace
+        #[structopt(parse(from_os_str))]
+        trace: PathBuf,
+
+        /// Directory the traced files are re-created in
+        #[structopt(long, parse(from_os_str))]
+        root: PathBuf,
+
+        /// Replay the syscalls, or print an fio job file that approximates
+        /// the workload
+        #[structopt(long, default_value = "run", possible_values = ReplayFormat::VARIANTS)]
+        format: ReplayFormat,
+
+        /// Write the fio job file to this file instead of stdout
+        #[structopt(short, long, parse(from_os_str))]
+        output: Option<PathBuf>,
+
+        /// Divide the delays between the syscalls by this factor (0 replays
+        /// them without delays)
+        #[structopt(long, default_value = "1")]
+        speed: f64,
+
+        #[structopt(flatten)]
+        filter: FilterOpt,
+    },
+
+    /// Compare the I/O of two recorded traces
+    Diff {
+        /// The trace of the baseline run
+        #[structopt(parse(from_os_str))]
+        before: PathBuf,
+
+        /// The trace of the run that is compared against the baseline
+        #[structopt(parse(from_os_str))]
+        after: PathBuf,
+
+        #[structopt(flatten)]
+        filter: FilterOpt,
+
+        /// Report files whose open duration changed by more than this many
+        /// percent
+        #[structopt(long, default_value = "20")]
+        duration_threshold: f64,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+pub struct Source {
+    /// Analyze a recorded bpftrace output instead of tracing a program
+    #[structopt(long, parse(from_os_str))]
+    pub trace: Option<PathBuf>,
+
     /// Input file
-    #[structopt(parse(from_os_str))]
-    pub input: PathBuf,
+    #[structopt(parse(from_os_str), required_unless_one = &["trace", "pid"])]
+    pub input: Option<PathBuf>,
+
+    /// Attach to the running process instead of starting a program. The trace
+    /// stops when the process exits, or on Ctrl-C
+    #[structopt(long, conflicts_with = "trace")]
+    pub pid: Option<pid_t>,
+
+    #[structopt(flatten)]
+    pub filter: FilterOpt,
+
+    /// Record the user-space stack of every open to group the files by call
+    /// site
+    #[structopt(long)]
+    pub stacks: bool,
+
+    /// Also record the user-space stack of every read and write
+    #[structopt(long)]
+    pub io_stacks: bool,
+}
+
+impl Source {
+    /// Traces the target program, or loads the recorded trace.
+    pub fn tracer(&self) -> anyhow::Result<BpfTracer> {
+        let filter = self.filter.path_filter()?;
+        let options = TraceOptions {
+            filter: self.filter.bpf_filter.then_some(&filter),
+            stacks: match (self.stacks, self.io_stacks) {
+                (_, true) => Stacks::All,
+                (true, false) => Stacks::Open,
+                (false, false) => Stacks::None,
+            },
+        };
+
+        match (&self.trace, self.pid, &self.input) {
+            (Some(trace), _, _) => BpfTracer::load(trace),
+            (None, Some(pid), _) => BpfTracer::attach(pid, &options),
+            (None, None, Some(input)) => BpfTracer::trace(input, &options),
+            (None, None, None) => Err(anyhow::anyhow!("No program or trace specified")),
+        }
+    }
+}
+
+#[derive(Debug, StructOpt)]
+pub struct FilterOpt {
+    /// Only report paths matching the glob (or `regex:<pattern>`)
+    #[structopt(long, number_of_values = 1)]
+    pub include: Vec<String>,
+
+    /// Don't report paths matching the glob (or `regex:<pattern>`)
+    #[structopt(long, number_of_values = 1)]
+    pub exclude: Vec<String>,
+
+    /// Don't report the files opened by the dynamic linker
+    #[structopt(long)]
+    pub exclude_loader: bool,
+
+    /// Don't report the I/O of inherited fds (e.g. stdout and stderr)
+    #[structopt(long)]
+    pub exclude_inherited: bool,
+
+    /// Evaluate prefix filters (e.g. `/usr/lib/*`) in the bpftrace script to
+    /// reduce the number of events
+    #[structopt(long)]
+    pub bpf_filter: bool,
+}
+
+impl FilterOpt {
+    pub fn path_filter(&self) -> anyhow::Result<PathFilter> {
+        let filter = PathFilter::new(&self.include, &self.exclude)?;
+        if self.exclude_loader {
+            Ok(filter.exclude_loader())
+        } else {
+            Ok(filter)
+        }
+    }
 }
 
 pub mod analysis;
+pub mod elf;
+pub mod fdinfo;
+pub mod replay;
+pub mod stack;
 pub mod syscall;
 pub mod tracer;
+pub mod tui;
 
-fn main() -> anyhow::Result<()> {
-    env_logger::init();
-
-    let args = Opt::from_args();
-
+fn analyze(source: &Source, debug: bool) -> anyhow::Result<Analysis> {
     // 1. Trace the target program
     //
-    let tracer = BpfTracer::trace(&args.input).unwrap();
-    if args.debug {
+    let tracer = source.tracer()?;
+    if debug {
         tracer.debug_print();
         tracer.print_to_file("debug.txt");
     }
 
     // 2. Analyze the trace
     //
-    let analysis = Analysis::new(tracer.take_syscalls());
-    analysis.print_result();
+    filtered(Analysis::new(tracer.take_syscalls()), &source.filter)
+}
+
+/// Removes the files that don't match the filter from the analysis.
+fn filtered(mut analysis: Analysis, filter: &FilterOpt) -> anyhow::Result<Analysis> {
+    if filter.exclude_inherited {
+        analysis.remove_inherited();
+    }
+
+    let filter = filter.path_filter()?;
+    if !filter.is_empty() {
+        analysis.retain_files(&filter);
+    }
+
+    Ok(analysis)
+}
+
+fn main() -> anyhow::Result<()> {
+    env_logger::init();
+
+    let args = Opt::from_args();
+    match &args.cmd {
+        Some(Command::Tui { source }) => {
+            let analysis = analyze(source, args.debug)?;
+            tui::run(&analysis)?;
+        }
+        Some(Command::Tree {
+            source,
+            depth,
+            weight,
+            folded,
+        }) => {
+            let tree = analyze(source, args.debug)?.dir_tree(*depth);
+            if *folded {
+                print!("{}", tree.folded(*weight));
+            } else {
+                println!("{}", tree.table(*weight));
+            }
+        }
+        Some(Command::Check { source, budget }) => {
+            let budget = Budget::load(budget)?;
+            let violations = analyze(source, args.debug)?.print_budget_check(&budget);
+            if violations > 0 {
+                std::process::exit(1);
+            }
+        }
+        Some(Command::Policy {
+            source,
+            format,
+            threshold,
+            name,
+        }) => {
+            let name = name
+                .clone()
+                .or_else(|| Some(source.input.as_ref()?.display().to_string()))
+                .unwrap_or_else(|| "app".into());
+            let policy = analyze(source, args.debug)?.policy(*threshold);
+            print!("{}", policy.render(*format, &name));
+        }
+        Some(Command::Deps {
+            source,
+            format,
+            target,
+            cwd,
+            output,
+        }) => {
+            let cwd = match cwd {
+                Some(cwd) => cwd.clone(),
+                None => std::env::current_dir()?,
+            };
+            let deps = analyze(source, args.debug)?.deps(&cwd);
+            let deps = deps.render(*format, target.as_deref());
+            match output {
+                Some(output) => std::fs::write(output, deps)?,
+                None => print!("{deps}"),
+            }
+        }
+        Some(Command::Manifest {
+            source,
+            format,
+            root,
+            from,
+            output,
+        }) => {
+            let manifest = analyze(source, args.debug)?.manifest(root);
+            for path in &manifest.missing {
+                log::warn!("Skipping missing file: {}", path.display());
+            }
+            let manifest = manifest.render(*format, from);
+            match output {
+                Some(output) => std::fs::write(output, manifest)?,
+                None => print!("{manifest}"),
+            }
+        }
+        Some(Command::Replay {
+            trace,
+            root,
+            format,
+            output,
+            speed,
+            filter,
+        }) => {
+            let analysis = filtered(
+                Analysis::new(BpfTracer::load(trace)?.take_syscalls()),
+                filter,
+            )?;
+            let workload = Workload::new(&analysis);
+            if *format == ReplayFormat::Fio {
+                let job = workload.fio_job(root);
+                match output {
+                    Some(output) => std::fs::write(output, job)?,
+                    None => print!("{job}"),
+                }
+                return Ok(());
+            }
+
+            workload.prepare(root)?;
+
+            let start = std::time::Instant::now();
+            let stats = workload.replay(root, *speed)?;
+            println!(
+                "Replayed {} opens, {} reads ({} bytes) and {} writes ({} bytes) of {} threads in \
+                 {}",
+                stats.opens,
+                stats.reads,
+                stats.read_bytes,
+                stats.writes,
+                stats.write_bytes,
+                workload.threads.len(),
+                analysis::utils::format_ns(start.elapsed().as_nanos() as u64)
+            );
+        }
+        Some(Command::Diff {
+            before,
+            after,
+            filter,
+            duration_threshold,
+        }) => {
+            let before = filtered(
+                Analysis::new(BpfTracer::load(before)?.take_syscalls()),
+                filter,
+            )?;
+            let after = filtered(
+                Analysis::new(BpfTracer::load(after)?.take_syscalls()),
+                filter,
+            )?;
+            before.print_diff(&after, *duration_threshold);
+        }
+        None => {
+            let analysis = analyze(&args.source, args.debug)?;
+            analysis.print_summary(args.report.top, args.report.sort_by);
+            analysis.print_inherited_fds();
+            analysis.print_channels();
+            analysis.print_durability();
+            analysis.print_metadata(args.report.top);
+            analysis.print_small_io(&args.report.small_io_config(), args.report.top);
+            analysis.print_rereads(args.report.rereads_per_thread);
+            analysis.print_idle_sessions(&args.report.idle_config());
+            analysis.print_call_sites(args.report.top);
+            analysis.print_result();
+            if args.report.latency {
+                analysis.print_latency();
+            }
+        }
+    }
 
     Ok(())
 }
@@ -0,0 +1,641 @@
+use crate::{
+    analysis::{
+        file::{FileEvent, FileSession},
+        utils, Analysis,
+    },
+    syscall::{open_flags::*, tid_t},
+};
+use anyhow::Context;
+use itertools::Itertools;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    fs::{File, OpenOptions},
+    io::{Read, Write},
+    os::unix::fs::OpenOptionsExt,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+/// The flags of the traced `open` that are passed on, all others (e.g.
+/// `O_EXCL` or `O_DIRECT`) could make the replay fail.
+const REPLAYED_FLAGS: u64 = O_CREAT | O_TRUNC | O_APPEND;
+
+/// What is done with the workload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplayFormat {
+    /// Replay the syscalls.
+    Run,
+    /// Print an fio job file that approximates the workload.
+    Fio,
+}
+
+impl ReplayFormat {
+    pub const VARIANTS: &'static [&'static str] = &["run", "fio"];
+}
+
+impl FromStr for ReplayFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "run" => Ok(ReplayFormat::Run),
+            "fio" => Ok(ReplayFormat::Fio),
+            _ => Err(anyhow::anyhow!("Unknown replay format: {s}")),
+        }
+    }
+}
+
+/// The number of block sizes in `bssplit`, the rarer ones are dropped.
+const MAX_BLOCK_SIZES: usize = 8;
+
+/// A syscall of the replayed workload. Files are referenced by the index of
+/// their session in [`ThreadWorkload::sessions`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Op {
+    Open { session: usize },
+    Read { session: usize, len: usize },
+    Write { session: usize, len: usize },
+    Close { session: usize },
+}
+
+impl Op {
+    pub const fn session(&self) -> usize {
+        match self {
+            Op::Open { session }
+            | Op::Read { session, .. }
+            | Op::Write { session, .. }
+            | Op::Close { session } => *session,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThreadWorkload {
+    pub tid: tid_t,
+    /// The path and flags of the opened files.
+    pub sessions: Vec<(String, u64)>,
+    /// The syscalls with the time they were issued, relative to the start of
+    /// the trace (in ns).
+    pub ops: Vec<(u64, Op)>,
+}
+
+/// The file I/O of a trace, which can be replayed in another directory.
+#[derive(Debug, Default)]
+pub struct Workload {
+    /// The size of the files that have to exist before the replay. Directories
+    /// have no size.
+    pub files: BTreeMap<String, Option<usize>>,
+    pub threads: Vec<ThreadWorkload>,
+}
+
+/// What the replay did, summed over all threads.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ReplayStats {
+    pub opens: usize,
+    pub reads: usize,
+    pub writes: usize,
+    pub read_bytes: usize,
+    pub write_bytes: usize,
+}
+
+impl ReplayStats {
+    fn add(&mut self, other: &ReplayStats) {
+        self.opens += other.opens;
+        self.reads += other.reads;
+        self.writes += other.writes;
+        self.read_bytes += other.read_bytes;
+        self.write_bytes += other.write_bytes;
+    }
+}
+
+impl Workload {
+    /// Collects the sessions of all threads. Inherited fds (e.g. the standard
+    /// streams) and pseudo filesystems are skipped.
+    pub fn new(analysis: &Analysis) -> Self {
+        let mut workload = Workload::default();
+
+        let sessions = analysis.threads().map(|thread| {
+            let sessions = thread
+                .files()
+                .values()
+                .flat_map(|info| &info.sessions)
+                .chain(thread.leaks())
+                .filter(|s| !s.is_inherited() && !utils::is_pseudo_fs(Path::new(&s.path)))
+                .collect::<Vec<_>>();
+            (thread.tid(), sessions)
+        });
+        let sessions = sessions.collect::<Vec<_>>();
+
+        let start_ts = sessions
+            .iter()
+            .flat_map(|(_, sessions)| sessions.iter().map(|s| s.open_ts))
+            .min()
+            .unwrap_or_default();
+
+        for (tid, sessions) in sessions {
+            let mut thread = ThreadWorkload {
+                tid,
+                sessions: Vec::new(),
+                ops: Vec::new(),
+            };
+
+            for session in sessions {
+                // Keeps the paths with `..` in the root.
+                let path = utils::clean_path(&Path::new("/").join(&session.path));
+                let path = path.to_string_lossy().to_string();
+                if session.is_directory() {
+                    workload.files.insert(path, None);
+                    continue;
+                }
+
+                // Without offsets, the reads are assumed to be sequential.
+                let size = workload.files.entry(path.clone()).or_default();
+                *size = (*size).max(Some(read_bytes(session)));
+
+                let index = thread.sessions.len();
+                thread.sessions.push((path, session.flags));
+                thread.ops.extend(ops(session, index, start_ts));
+            }
+
+            thread.ops.sort_by_key(|(ts, _)| *ts);
+            workload.threads.push(thread);
+        }
+
+        // A path can't be a file and a directory, which happens if bpftrace
+        // truncated it. The sessions of the file are dropped.
+        let dirs = workload
+            .files
+            .keys()
+            .flat_map(|path| Path::new(path).ancestors().skip(1))
+            .map(|dir| dir.to_string_lossy().to_string())
+            .collect::<HashSet<_>>();
+        for (path, size) in &mut workload.files {
+            if size.is_some() && dirs.contains(path) {
+                log::warn!("Skipping {path}, which is also a directory");
+                *size = None;
+            }
+        }
+        for thread in &mut workload.threads {
+            let files = &workload.files;
+            let sessions = &thread.sessions;
+            thread
+                .ops
+                .retain(|(_, op)| files[&sessions[op.session()].0].is_some());
+        }
+
+        workload
+    }
+
+    /// Creates the files in `root`, with the size needed for the reads.
+    pub fn prepare(&self, root: &Path) -> anyhow::Result<()> {
+        let chunk = vec![0xa5u8; 1 << 20];
+        for (path, size) in &self.files {
+            let path = host_path(root, path);
+            let Some(size) = size else {
+                std::fs::create_dir_all(&path)
+                    .with_context(|| format!("Failed to create {}", path.display()))?;
+                continue;
+            };
+
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            let mut file = File::create(&path)
+                .with_context(|| format!("Failed to create {}", path.display()))?;
+            let mut remaining = *size;
+            while remaining > 0 {
+                let len = remaining.min(chunk.len());
+                file.write_all(&chunk[..len])?;
+                remaining -= len;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Replays the syscalls of every thread in its own thread. The delays
+    /// between the syscalls are divided by `speed`, 0 replays them as fast as
+    /// possible.
+    pub fn replay(&self, root: &Path, speed: f64) -> anyhow::Result<ReplayStats> {
+        let start = Instant::now();
+        let stats = std::thread::scope(|scope| {
+            let handles = self
+                .threads
+                .iter()
+                .map(|thread| scope.spawn(move || thread.replay(root, speed, start)))
+                .collect::<Vec<_>>();
+
+            handles
+                .into_iter()
+                .map(|handle| handle.join().expect("replay thread panicked"))
+                .collect::<anyhow::Result<Vec<_>>>()
+        })?;
+
+        let mut total = ReplayStats::default();
+        for stats in &stats {
+            total.add(stats);
+        }
+        Ok(total)
+    }
+}
+
+/// The access pattern of a file, aggregated over all threads.
+#[derive(Debug, Default)]
+struct FilePattern {
+    /// The number of reads and writes by size.
+    reads: BTreeMap<usize, usize>,
+    writes: BTreeMap<usize, usize>,
+    threads: BTreeSet<tid_t>,
+    /// When the file was first opened, relative to the start of the trace.
+    start_ts: u64,
+}
+
+impl FilePattern {
+    fn bytes(&self) -> usize {
+        let sum =
+            |sizes: &BTreeMap<usize, usize>| sizes.iter().map(|(size, n)| size * n).sum::<usize>();
+        sum(&self.reads) + sum(&self.writes)
+    }
+}
+
+impl Workload {
+    fn file_patterns(&self) -> BTreeMap<&str, FilePattern> {
+        let mut files = BTreeMap::<&str, FilePattern>::new();
+        for thread in &self.threads {
+            for (ts, op) in &thread.ops {
+                let path = thread.sessions[op.session()].0.as_str();
+                let pattern = files.entry(path).or_insert_with(|| FilePattern {
+                    start_ts: *ts,
+                    ..Default::default()
+                });
+                pattern.threads.insert(thread.tid);
+                pattern.start_ts = pattern.start_ts.min(*ts);
+
+                match *op {
+                    Op::Read { len, .. } if len > 0 => *pattern.reads.entry(len).or_default() += 1,
+                    Op::Write { len, .. } if len > 0 => {
+                        *pattern.writes.entry(len).or_default() += 1
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        files
+    }
+
+    /// Returns an fio job file with a job per file, which reads and writes
+    /// the traced amount of data with the traced block sizes. Each thread
+    /// accessing the file is a separate process (`numjobs`).
+    ///
+    /// The file offsets aren't traced, so the access is assumed to be
+    /// sequential, as it is for `read` and `write` without seeking.
+    pub fn fio_job(&self, root: &Path) -> String {
+        let patterns = self.file_patterns();
+        let (patterns, skipped): (Vec<_>, Vec<_>) = patterns
+            .into_iter()
+            .partition(|(_, pattern)| pattern.bytes() > 0);
+
+        let mut output = String::new();
+        output.push_str(
+            "# The file offsets aren't traced, the access is assumed to be sequential.\n",
+        );
+        if !skipped.is_empty() {
+            output.push_str(&format!(
+                "# Skipped {} files without reads or writes.\n",
+                skipped.len()
+            ));
+        }
+        output.push_str("\n[global]\n");
+        output.push_str(&format!(
+            "directory={}\n",
+            fio_escape(&root.to_string_lossy())
+        ));
+        output.push_str("ioengine=psync\n");
+
+        for (path, pattern) in patterns {
+            let relative = path.trim_start_matches('/');
+            let reads = pattern.reads.values().sum::<usize>();
+            let writes = pattern.writes.values().sum::<usize>();
+            let rw = match (reads, writes) {
+                (_, 0) => "read",
+                (0, _) => "write",
+                _ => "rw",
+            };
+            let jobs = pattern.threads.len();
+            let io_size = pattern.bytes().div_ceil(jobs);
+            // Files that are only written are laid out with the written size,
+            // reads beyond the file size start over at the beginning.
+            let size = match self.files.get(path).copied().flatten() {
+                Some(size) if size > 0 => size,
+                _ => io_size,
+            };
+
+            output.push_str(&format!("\n[{relative}]\n"));
+            output.push_str(&format!("filename={}\n", fio_escape(relative)));
+            output.push_str(&format!("rw={rw}\n"));
+            if reads > 0 && writes > 0 {
+                output.push_str(&format!("rwmixread={}\n", reads * 100 / (reads + writes)));
+            }
+            output.push_str(&format!(
+                "bssplit={}\n",
+                [&pattern.reads, &pattern.writes]
+                    .into_iter()
+                    .filter(|sizes| !sizes.is_empty())
+                    .map(bssplit)
+                    .join(",")
+            ));
+            output.push_str(&format!("filesize={size}\n"));
+            output.push_str(&format!("io_size={io_size}\n"));
+            output.push_str(&format!("numjobs={jobs}\n"));
+            if pattern.start_ts > 0 {
+                output.push_str(&format!("startdelay={}us\n", pattern.start_ts / 1000));
+            }
+        }
+
+        output
+    }
+}
+
+/// Returns the block size distribution for fio (e.g. `4096/75:512/25`),
+/// ordered by the number of syscalls.
+fn bssplit(sizes: &BTreeMap<usize, usize>) -> String {
+    let total = sizes.values().sum::<usize>();
+    let sorted = sizes
+        .iter()
+        .sorted_by_key(|(size, count)| (std::cmp::Reverse(**count), **size))
+        .collect::<Vec<_>>();
+    let Some((&most_common, _)) = sorted.first() else {
+        return String::new();
+    };
+
+    // Sizes with less than 1% of the syscalls are dropped. If that are all of
+    // them, the most common size is used for all I/O.
+    let mut splits = sorted
+        .iter()
+        .take(MAX_BLOCK_SIZES)
+        .map(|(size, count)| (**size, *count * 100 / total))
+        .filter(|(_, percent)| *percent > 0)
+        .collect::<Vec<_>>();
+    if splits.is_empty() {
+        splits.push((most_common, 0));
+    }
+
+    // The percentages have to add up to 100, the rest goes to the most common
+    // size.
+    let rest = 100 - splits.iter().map(|(_, percent)| percent).sum::<usize>();
+    if let Some(split) = splits.iter_mut().find(|(size, _)| *size == most_common) {
+        split.1 += rest;
+    }
+
+    splits
+        .iter()
+        .map(|(size, percent)| format!("{size}/{percent}"))
+        .join(":")
+}
+
+/// Escapes the colons, which separate multiple files in fio.
+fn fio_escape(path: &str) -> String {
+    path.replace(':', "\\:")
+}
+
+impl ThreadWorkload {
+    fn replay(&self, root: &Path, speed: f64, start: Instant) -> anyhow::Result<ReplayStats> {
+        let mut stats = ReplayStats::default();
+        let mut files = self.sessions.iter().map(|_| None).collect::<Vec<_>>();
+        let mut buffer = Vec::new();
+
+        for (ts, op) in &self.ops {
+            if speed > 0.0 {
+                let due = start + Duration::from_nanos((*ts as f64 / speed) as u64);
+                std::thread::sleep(due.saturating_duration_since(Instant::now()));
+            }
+
+            match *op {
+                Op::Open { session } => {
+                    let (path, flags) = &self.sessions[session];
+                    let path = host_path(root, path);
+                    let file = options(*flags)
+                        .open(&path)
+                        .with_context(|| format!("Failed to open {}", path.display()))?;
+                    files[session] = Some(file);
+                    stats.opens += 1;
+                }
+                Op::Read { session, len } => {
+                    let file = file(&mut files, session)?;
+                    buffer.resize(buffer.len().max(len), 0);
+                    stats.read_bytes += file.read(&mut buffer[..len])?;
+                    stats.reads += 1;
+                }
+                Op::Write { session, len } => {
+                    let file = file(&mut files, session)?;
+                    buffer.resize(buffer.len().max(len), 0);
+                    stats.write_bytes += file.write(&buffer[..len])?;
+                    stats.writes += 1;
+                }
+                Op::Close { session } => {
+                    files[session] = None;
+                }
+            }
+        }
+
+        Ok(stats)
+    }
+}
+
+fn file(files: &mut [Option<File>], session: usize) -> anyhow::Result<&mut File> {
+    files[session]
+        .as_mut()
+        .ok_or_else(|| anyhow::anyhow!("Session {session} is not open"))
+}
+
+/// Returns the path of the traced file in `root`.
+fn host_path(root: &Path, path: &str) -> PathBuf {
+    root.join(path.trim_start_matches('/'))
+}
+
+fn options(flags: u64) -> OpenOptions {
+    let mut options = OpenOptions::new();
+    match flags & O_ACCMODE {
+        O_WRONLY => options.write(true),
+        O_RDWR => options.read(true).write(true),
+        _ => options.read(true),
+    };
+    options.custom_flags((flags & REPLAYED_FLAGS) as i32);
+    options
+}
+
+fn read_bytes(session: &FileSession) -> usize {
+    session
+        .events
+        .iter()
+        .filter(|event| event.is_read())
+        .map(FileEvent::bytes)
+        .sum()
+}
+
+fn ops(session: &FileSession, index: usize, start_ts: u64) -> Vec<(u64, Op)> {
+    let ts = |ts: u64| ts.saturating_sub(start_ts);
+
+    let mut ops = vec![(ts(session.open_ts), Op::Open { session: index })];
+    for event in &session.events {
+        let op = match event {
+            // The requested size is replayed, the file is only as large as
+            // the traced reads returned.
+            FileEvent::Read { requested, .. } => Op::Read {
+                session: index,
+                len: *requested,
+            },
+            FileEvent::Write { bytes, .. } => Op::Write {
+                session: index,
+                len: *bytes,
+            },
+            // Copies are replayed as a read or write of the copied bytes.
+            FileEvent::Transfer {
+                bytes, is_source, ..
+            } => {
+                if *is_source {
+                    Op::Read {
+                        session: index,
+                        len: *bytes,
+                    }
+                } else {
+                    Op::Write {
+                        session: index,
+                        len: *bytes,
+                    }
+                }
+            }
+            // Syncs, truncates and allocations aren't replayed.
+            FileEvent::Sync { .. } | FileEvent::Truncate { .. } | FileEvent::Allocate { .. } => {
+                continue
+            }
+        };
+        ops.push((ts(event.start_ts()), op));
+    }
+    if session.close_ts != 0 {
+        ops.push((ts(session.close_ts), Op::Close { session: index }));
+    }
+
+    ops
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::analyze;
+
+    #[test]
+    fn test_replay() {
+        let analysis = analyze(&[
+            "Attaching 13 probes...",
+            "1000;10;10;execve;/bin/app",
+            "1100;10;10;openat;4294967196;/etc/app.conf;524288",
+            "1200;10;10;openat_exit;3",
+            "1300;10;10;read;3;4096",
+            "1400;10;10;read_exit;4096",
+            "1500;10;10;read;3;4096",
+            "1600;10;10;read_exit;100",
+            "1900;10;10;close;3",
+            "2000;10;10;close_exit;0",
+            "2100;10;11;openat;4294967196;/var/log/app.log;577",
+            "2200;10;11;openat_exit;4",
+            "2300;10;11;write;4;512",
+            "2400;10;11;write_exit;512",
+            "2500;10;11;write;4;512",
+            "2600;10;11;write_exit;512",
+            "2700;10;10;openat;4294967196;/proc/self/maps;524288",
+            "2800;10;10;openat_exit;3",
+        ]);
+
+        let workload = Workload::new(&analysis);
+        assert_eq!(
+            workload.files,
+            BTreeMap::from([
+                ("/etc/app.conf".into(), Some(4196)),
+                ("/var/log/app.log".into(), Some(0)),
+            ])
+        );
+        assert_eq!(workload.threads.len(), 2);
+        let read = Op::Read {
+            session: 0,
+            len: 4096,
+        };
+        assert_eq!(
+            workload.threads[0].ops,
+            [
+                (0, Op::Open { session: 0 }),
+                (200, read.clone()),
+                (400, read),
+                (800, Op::Close { session: 0 }),
+            ]
+        );
+
+        let root = tempfile::tempdir().unwrap();
+        workload.prepare(root.path()).unwrap();
+        let stats = workload.replay(root.path(), 0.0).unwrap();
+        assert_eq!(
+            stats,
+            ReplayStats {
+                opens: 2,
+                reads: 2,
+                writes: 2,
+                read_bytes: 4196,
+                write_bytes: 1024,
+            }
+        );
+
+        let log = root.path().join("var/log/app.log");
+        assert_eq!(std::fs::metadata(log).unwrap().len(), 1024);
+    }
+
+    #[test]
+    fn test_fio_job() {
+        let mut trace = vec![
+            "Attaching 13 probes...".to_string(),
+            "1000;10;10;execve;/bin/db".into(),
+        ];
+        // Two threads read the same file, one of them also updates it
+        for (tid, fd) in [(10, 3), (11, 4)] {
+            let ts = tid * 100_000;
+            trace.push(format!("{ts};10;{tid};openat;4294967196;/var/db/data:1;2"));
+            trace.push(format!("{};10;{tid};openat_exit;{fd}", ts + 10));
+            for i in 0..8 {
+                let ts = ts + 100 + i * 100;
+                let (syscall, count) = match i {
+                    0..5 => ("read", 4096),
+                    5 => ("read", 512),
+                    _ => ("write", 4096),
+                };
+                if syscall == "write" && tid == 11 {
+                    continue;
+                }
+                trace.push(format!("{ts};10;{tid};{syscall};{fd};{count}"));
+                trace.push(format!("{};10;{tid};{syscall}_exit;{count}", ts + 10));
+            }
+            trace.push(format!("{};10;{tid};close;{fd}", ts + 1000));
+            trace.push(format!("{};10;{tid};close_exit;0", ts + 1010));
+        }
+        trace.push("1200000;10;10;openat;4294967196;/etc/db.conf;524288".into());
+        trace.push("1200010;10;10;openat_exit;3".into());
+        let analysis = analyze(&trace);
+
+        let workload = Workload::new(&analysis);
+        insta::assert_snapshot!(workload.fio_job(Path::new("/tmp/scratch")));
+    }
+
+    #[test]
+    fn test_bssplit() {
+        // The rest of the rounding goes to the most common size
+        let sizes = BTreeMap::from([(512, 1), (4096, 2)]);
+        assert_eq!(bssplit(&sizes), "4096/67:512/33");
+
+        // None of the sizes has 1% of the syscalls
+        let sizes = (1..=200).map(|size| (size * 512, 1)).collect();
+        assert_eq!(bssplit(&sizes), "512/100");
+
+        let sizes = (1..=200).map(|size| (size * 512, 1)).chain([(8192, 50)]);
+        assert_eq!(bssplit(&sizes.collect()), "8192/100");
+    }
+}
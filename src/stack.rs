@@ -0,0 +1,130 @@
+use crate::syscall::{RawSyscall, Syscall};
+use std::{collections::BTreeMap, fmt, path::Path};
+
+/// A frame of a user-space stack, as printed by bpftrace's `ustack(perf)`
+/// (e.g. `7f3c2a1b4e5f __libc_open64+67 (/usr/lib/libc.so.6)`).
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(test, derive(serde::Serialize))]
+pub struct Frame {
+    pub addr: u64,
+    /// The symbol (with offset), `None` if it couldn't be resolved.
+    pub symbol: Option<String>,
+    /// The binary or library the address belongs to.
+    pub module: Option<String>,
+}
+
+impl Frame {
+    pub fn parse(line: &str) -> Option<Self> {
+        let (addr, rest) = line.trim().split_once(' ').unwrap_or((line.trim(), ""));
+        let addr = u64::from_str_radix(addr.trim_start_matches("0x"), 16).ok()?;
+
+        let (symbol, module) = match rest.strip_suffix(')').and_then(|r| r.rsplit_once(" (")) {
+            Some((symbol, module)) => (symbol, Some(module)),
+            None => (rest, None),
+        };
+        let known = |s: &str| (!s.is_empty() && s != "[unknown]").then(|| s.to_string());
+
+        Some(Self {
+            addr,
+            symbol: known(symbol.trim()),
+            module: module.and_then(known),
+        })
+    }
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.symbol {
+            Some(symbol) => write!(f, "{symbol}")?,
+            None => write!(f, "0x{:x}", self.addr)?,
+        }
+        if let Some(module) = &self.module {
+            let name = Path::new(module).file_name().unwrap_or_default();
+            write!(f, " ({})", name.to_string_lossy())?;
+        }
+        Ok(())
+    }
+}
+
+/// Resolves the frames that bpftrace couldn't symbolize (usually because the
+/// process already exited) with `addr2line`.
+///
+/// This only works for addresses that don't need to be relocated, i.e. for
+/// non-PIE executables. All other frames are left as they are.
+pub fn symbolize(syscalls: &mut [Syscall]) {
+    let mut unresolved = BTreeMap::<String, Vec<u64>>::new();
+    for syscall in syscalls.iter() {
+        if let RawSyscall::Stack { frames } = &syscall.raw {
+            for frame in frames.iter().filter(|f| f.symbol.is_none()) {
+                if let Some(module) = &frame.module {
+                    unresolved
+                        .entry(module.clone())
+                        .or_default()
+                        .push(frame.addr);
+                }
+            }
+        }
+    }
+
+    let mut symbols = BTreeMap::new();
+    for (module, mut addrs) in unresolved {
+        if !Path::new(&module).exists() {
+            continue;
+        }
+        addrs.sort_unstable();
+        addrs.dedup();
+
+        let output = std::process::Command::new("addr2line")
+            .args(["-f", "-C", "-e", &module])
+            .args(addrs.iter().map(|addr| format!("0x{addr:x}")))
+            .output();
+        let output = match output {
+            Ok(output) if output.status.success() => output,
+            Ok(output) => {
+                log::debug!("addr2line failed for {module}: {output:?}");
+                continue;
+            }
+            Err(error) => {
+                log::debug!("Failed to run addr2line: {error}");
+                return;
+            }
+        };
+
+        // Each address results in two lines: the function and its location.
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        for (addr, function) in addrs.iter().zip(stdout.lines().step_by(2)) {
+            if function != "??" {
+                symbols.insert((module.clone(), *addr), function.to_string());
+            }
+        }
+    }
+
+    for syscall in syscalls.iter_mut() {
+        if let RawSyscall::Stack { frames } = &mut syscall.raw {
+            for frame in frames.iter_mut().filter(|f| f.symbol.is_none()) {
+                if let Some(module) = &frame.module {
+                    frame.symbol = symbols.get(&(module.clone(), frame.addr)).cloned();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_frame() {
+        let frame = Frame::parse("\t7f3c2a1b4e5f __libc_open64+67 (/usr/lib/libc.so.6)").unwrap();
+        assert_eq!(frame.addr, 0x7f3c2a1b4e5f);
+        assert_eq!(frame.symbol.as_deref(), Some("__libc_open64+67"));
+        assert_eq!(frame.to_string(), "__libc_open64+67 (libc.so.6)");
+
+        let frame = Frame::parse("        401136 [unknown] (/tmp/app)").unwrap();
+        assert_eq!(frame.symbol, None);
+        assert_eq!(frame.to_string(), "0x401136 (app)");
+
+        assert_eq!(Frame::parse("Attaching 1 probe..."), None);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::stack::Frame;
 use std::ops::{Deref, DerefMut};
 
 #[allow(non_camel_case_types)]
@@ -8,6 +9,48 @@ pub type tid_t = i32;
 #[allow(non_camel_case_types)]
 pub type fd_t = u64;
 
+/// `AT_FDCWD` (-100) as it is printed for the `dirfd` of `openat`.
+pub const AT_FDCWD: fd_t = 4294967196;
+
+/// Flags of `open` and `openat` (see `fcntl.h`).
+pub mod open_flags {
+    pub const O_ACCMODE: u64 = 0o3;
+    pub const O_WRONLY: u64 = 0o1;
+    pub const O_RDWR: u64 = 0o2;
+    pub const O_CREAT: u64 = 0o100;
+    pub const O_TRUNC: u64 = 0o1000;
+    pub const O_APPEND: u64 = 0o2000;
+    pub const O_DSYNC: u64 = 0o10000;
+    /// Includes `O_DSYNC`.
+    pub const O_SYNC: u64 = 0o4010000;
+    pub const O_DIRECTORY: u64 = 0o200000;
+    pub const O_CLOEXEC: u64 = 0o2000000;
+}
+
+/// Protection and flags of `mmap` (see `mman.h`).
+pub mod mmap_flags {
+    pub const PROT_READ: u64 = 0x1;
+    pub const PROT_WRITE: u64 = 0x2;
+    pub const PROT_EXEC: u64 = 0x4;
+    pub const MAP_SHARED: u64 = 0x1;
+    pub const MAP_PRIVATE: u64 = 0x2;
+    pub const MAP_FIXED: u64 = 0x10;
+}
+
+/// Domains and types of `socket` and `socketpair` (see `socket.h`), and the
+/// errors of `connect`.
+pub mod socket {
+    pub const AF_UNIX: u64 = 1;
+    pub const AF_INET: u64 = 2;
+    pub const AF_INET6: u64 = 10;
+    pub const SOCK_STREAM: u64 = 1;
+    pub const SOCK_DGRAM: u64 = 2;
+    /// The type is combined with `SOCK_NONBLOCK` and `SOCK_CLOEXEC`.
+    pub const SOCK_TYPE_MASK: u64 = 0xf;
+    /// Returned by `connect` on non-blocking sockets.
+    pub const EINPROGRESS: i64 = 115;
+}
+
 #[derive(Debug, PartialEq)]
 #[cfg_attr(test, derive(serde::Serialize))]
 pub struct Syscall {
@@ -15,6 +58,10 @@ pub struct Syscall {
     pub pid: pid_t,
     pub tid: tid_t,
     pub raw: RawSyscall,
+    /// Name of the thread (`comm`), `None` for traces that were recorded
+    /// without it.
+    #[cfg_attr(test, serde(skip_serializing_if = "Option::is_none"))]
+    pub comm: Option<String>,
 }
 
 impl Syscall {
@@ -28,7 +75,8 @@ impl Syscall {
             ts: parts.next()?.parse().ok()?,
             pid: parts.next()?.parse().ok()?,
             tid: parts.next()?.parse().ok()?,
-            raw: RawSyscall::from_parts_iter(parts)?,
+            raw: RawSyscall::from_parts_iter(&mut parts)?,
+            comm: parts.next().filter(|c| !c.is_empty()).map(String::from),
         })
     }
 }
@@ -50,12 +98,33 @@ impl DerefMut for Syscall {
 /// # Covered syscalls
 ///
 /// - File creation and opening: open, openat.
-/// - File descriptor operations: close, read, write.
+/// - File descriptor operations: close, read, write, dup, dup2, dup3, and
+///   fcntl with `F_DUPFD` or `F_DUPFD_CLOEXEC`.
+/// - Pipes and sockets: pipe, pipe2, socket, socketpair, accept, accept4,
+///   connect, bind. Their I/O is traced through read and write, and sendto,
+///   recvfrom, sendmsg and recvmsg are recorded as reads and writes.
+/// - Copies between fds: sendfile, splice, copy_file_range, tee.
+/// - File-backed mappings: mmap, munmap, msync.
+/// - Durability: fsync, fdatasync, sync_file_range, ftruncate, fallocate.
+/// - Metadata: statx, newfstatat, access, faccessat, faccessat2, readlink,
+///   readlinkat, getdents64.
+/// - Process creation: execve, fork (including threads).
+/// - Thread renames: prctl(PR_SET_NAME).
+///
+/// The fds the target inherited are recorded as [`RawSyscall::InheritedFd`]
+/// right after its `execve` (or [`RawSyscall::Attach`]).
+///
+/// With `--stacks`, the user-space stack of the calling thread is emitted
+/// right before the syscall it belongs to.
 #[derive(Debug, PartialEq)]
 #[cfg_attr(test, derive(serde::Serialize))]
 #[rustfmt::skip]
 pub enum RawSyscall {
     Execve { path: String },
+    Fork { child: pid_t },
+
+    Stack { frames: Vec<Frame> },
+    SetName { name: String },
 
     Open { path: String, flags: u64, mode: u64 },
     OpenExit { ret: i64 },
@@ -66,11 +135,87 @@ pub enum RawSyscall {
     Close { fd: fd_t },
     CloseExit { ret: i64 },
 
+    /// `dup`, and `fcntl` with `F_DUPFD` or `F_DUPFD_CLOEXEC`.
+    Dup { fd: fd_t },
+    /// `dup2` and `dup3`, which are traced as the same syscall.
+    Dup2 { fd: fd_t, newfd: fd_t },
+    DupExit { ret: i64 },
+
+    /// `pipe` and `pipe2`.
+    Pipe,
+    /// The fds are only valid if `ret` is 0.
+    PipeExit { ret: i64, read_fd: fd_t, write_fd: fd_t },
+
+    Socket { domain: u64, socket_type: u64 },
+    SocketExit { ret: i64 },
+
+    SocketPair { domain: u64, socket_type: u64 },
+    SocketPairExit { ret: i64, fd: fd_t, peer_fd: fd_t },
+
+    /// `accept` and `accept4`.
+    Accept { fd: fd_t },
+    AcceptExit { ret: i64 },
+
+    /// The address is a path for UNIX sockets (empty if abstract or unnamed),
+    /// and `ip:port` or `[ipv6]:port` otherwise.
+    Connect { fd: fd_t, address: String },
+    ConnectExit { ret: i64 },
+
+    Bind { fd: fd_t, address: String },
+    BindExit { ret: i64 },
+
+    /// The target process that fdtrace attached to, instead of its `execve`.
+    Attach { path: String },
+    /// An fd the target inherited from its parent (e.g. stdout), or that was
+    /// open when fdtrace attached. Recorded by fdtrace when the trace starts.
+    InheritedFd { fd: fd_t, flags: u64, pos: u64, path: String },
+
     Read { fd: fd_t, count: usize },
     ReadExit { count: i64 },
 
     Write { fd: fd_t, count: usize },
     WriteExit { count: i64 },
+
+    /// `sendfile`, `splice`, `copy_file_range` and `tee`, which copy from
+    /// `fd_in` to `fd_out` without a buffer in user space.
+    Transfer { fd_in: fd_t, fd_out: fd_t, count: usize },
+    TransferExit { count: i64 },
+
+    /// Only file-backed mappings are traced.
+    Mmap { fd: fd_t, len: u64, prot: u64, flags: u64, offset: u64 },
+    /// The address of the mapping, or the negative errno.
+    MmapExit { ret: i64 },
+
+    Munmap { addr: u64, len: u64 },
+    MunmapExit { ret: i64 },
+
+    Msync { addr: u64, len: u64, flags: u64 },
+    MsyncExit { ret: i64 },
+
+    Fsync { fd: fd_t },
+    Fdatasync { fd: fd_t },
+    SyncFileRange { fd: fd_t, offset: u64, len: u64 },
+    /// The exit of `fsync`, `fdatasync` and `sync_file_range`.
+    SyncExit { ret: i64 },
+
+    Ftruncate { fd: fd_t, length: u64 },
+    FtruncateExit { ret: i64 },
+
+    Fallocate { fd: fd_t, mode: u64, offset: u64, len: u64 },
+    FallocateExit { ret: i64 },
+
+    /// `statx` and `newfstatat`. An empty path refers to `dirfd` itself.
+    Stat { dirfd: fd_t, path: String },
+    /// `access`, `faccessat` and `faccessat2`.
+    Access { dirfd: fd_t, path: String },
+    /// `readlink` and `readlinkat`.
+    Readlink { dirfd: fd_t, path: String },
+    /// The exit of `stat`, `access` and `readlink`.
+    MetadataExit { ret: i64 },
+
+    /// `getdents64`.
+    Getdents { fd: fd_t },
+    GetdentsExit { ret: i64 },
 }
 
 impl RawSyscall {
@@ -91,6 +236,11 @@ impl RawSyscall {
 
         match parts.next()? {
             "execve" => parse_syscall!(Execve, path),
+            "fork" => parse_syscall!(Fork, child),
+
+            // The frames are printed on the following lines
+            "ustack" => Some(RawSyscall::Stack { frames: Vec::new() }),
+            "set_name" => parse_syscall!(SetName, name),
 
             "open" => parse_syscall!(Open, path, flags, mode),
             "open_exit" => parse_syscall!(OpenExit, ret),
@@ -101,12 +251,68 @@ impl RawSyscall {
             "close" => parse_syscall!(Close, fd),
             "close_exit" => parse_syscall!(CloseExit, ret),
 
+            "dup" => parse_syscall!(Dup, fd),
+            "dup2" => parse_syscall!(Dup2, fd, newfd),
+            "dup_exit" => parse_syscall!(DupExit, ret),
+
+            "pipe" => Some(RawSyscall::Pipe),
+            "pipe_exit" => parse_syscall!(PipeExit, ret, read_fd, write_fd),
+
+            "socket" => parse_syscall!(Socket, domain, socket_type),
+            "socket_exit" => parse_syscall!(SocketExit, ret),
+
+            "socketpair" => parse_syscall!(SocketPair, domain, socket_type),
+            "socketpair_exit" => parse_syscall!(SocketPairExit, ret, fd, peer_fd),
+
+            "accept" => parse_syscall!(Accept, fd),
+            "accept_exit" => parse_syscall!(AcceptExit, ret),
+
+            "connect" => parse_syscall!(Connect, fd, address),
+            "connect_exit" => parse_syscall!(ConnectExit, ret),
+
+            "bind" => parse_syscall!(Bind, fd, address),
+            "bind_exit" => parse_syscall!(BindExit, ret),
+
+            "attach" => parse_syscall!(Attach, path),
+            "inherited_fd" => parse_syscall!(InheritedFd, fd, flags, pos, path),
+
             "read" => parse_syscall!(Read, fd, count),
             "read_exit" => parse_syscall!(ReadExit, count),
 
             "write" => parse_syscall!(Write, fd, count),
             "write_exit" => parse_syscall!(WriteExit, count),
 
+            "transfer" => parse_syscall!(Transfer, fd_in, fd_out, count),
+            "transfer_exit" => parse_syscall!(TransferExit, count),
+
+            "mmap" => parse_syscall!(Mmap, fd, len, prot, flags, offset),
+            "mmap_exit" => parse_syscall!(MmapExit, ret),
+
+            "munmap" => parse_syscall!(Munmap, addr, len),
+            "munmap_exit" => parse_syscall!(MunmapExit, ret),
+
+            "msync" => parse_syscall!(Msync, addr, len, flags),
+            "msync_exit" => parse_syscall!(MsyncExit, ret),
+
+            "fsync" => parse_syscall!(Fsync, fd),
+            "fdatasync" => parse_syscall!(Fdatasync, fd),
+            "sync_file_range" => parse_syscall!(SyncFileRange, fd, offset, len),
+            "sync_exit" => parse_syscall!(SyncExit, ret),
+
+            "ftruncate" => parse_syscall!(Ftruncate, fd, length),
+            "ftruncate_exit" => parse_syscall!(FtruncateExit, ret),
+
+            "fallocate" => parse_syscall!(Fallocate, fd, mode, offset, len),
+            "fallocate_exit" => parse_syscall!(FallocateExit, ret),
+
+            "stat" => parse_syscall!(Stat, dirfd, path),
+            "access" => parse_syscall!(Access, dirfd, path),
+            "readlink" => parse_syscall!(Readlink, dirfd, path),
+            "metadata_exit" => parse_syscall!(MetadataExit, ret),
+
+            "getdents" => parse_syscall!(Getdents, fd),
+            "getdents_exit" => parse_syscall!(GetdentsExit, ret),
+
             _ => None,
         }
     }
@@ -139,4 +345,14 @@ mod tests {
         let syscall = RawSyscall::from_parts(parts).unwrap();
         assert_eq!(syscall, RawSyscall::ReadExit { count: 832 });
     }
+
+    #[test]
+    fn test_parse_comm() {
+        let syscall = Syscall::from_parts("1000;10;11;read_exit;832;tokio-runtime-w").unwrap();
+        assert_eq!(syscall.raw, RawSyscall::ReadExit { count: 832 });
+        assert_eq!(syscall.comm.as_deref(), Some("tokio-runtime-w"));
+
+        let syscall = Syscall::from_parts("1000;10;11;read_exit;832").unwrap();
+        assert_eq!(syscall.comm, None);
+    }
 }
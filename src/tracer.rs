@@ -1,44 +1,326 @@
-use crate::syscall::{RawSyscall, Syscall};
-use std::{io::Write, path::Path};
+use crate::{
+    analysis::filter::PathFilter,
+    fdinfo::{self, FdInfo},
+    stack,
+    syscall::{pid_t, RawSyscall, Syscall},
+};
+use itertools::Itertools;
+use std::{
+    collections::HashSet,
+    io::Write,
+    path::Path,
+    process::{Command, Stdio},
+    time::{Duration, Instant},
+};
 use tempfile::NamedTempFile;
 
+/// How long to wait for bpftrace to fork the traced program.
+const CHILD_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The syscalls the user-space stack is recorded for.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Stacks {
+    #[default]
+    None,
+    /// Only `open` and `openat`.
+    Open,
+    /// Opens, reads, writes and transfers.
+    All,
+}
+
+/// Options that change what the bpftrace script records.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TraceOptions<'a> {
+    /// If set, its prefix rules are evaluated by bpftrace, so that the events
+    /// of these files are never emitted.
+    pub filter: Option<&'a PathFilter>,
+    pub stacks: Stacks,
+}
+
 pub struct BpfTracer {
     syscalls: Vec<Syscall>,
 }
 
 impl BpfTracer {
-    pub fn trace(program: &Path) -> anyhow::Result<Self> {
+    /// Traces the program with the script generated from the options.
+    pub fn trace(program: &Path, options: &TraceOptions) -> anyhow::Result<Self> {
+        let mut command = Command::new("bpftrace");
+        command.arg("-c").arg(program);
+
+        // bpftrace forks the program right away, but it only calls `execve`
+        // once the probes are attached. Until then, the fds of the child are
+        // the ones the program starts with.
+        let (output, fds) = Self::run(command, options, |pid| match Self::wait_for_child(pid) {
+            Some(child) => fdinfo::snapshot(child, true),
+            None => {
+                log::warn!("Failed to find the program started by bpftrace");
+                Vec::new()
+            }
+        })?;
+
+        Self::finish(Self::insert_inherited_fds(&output, &fds))
+    }
+
+    /// Traces the running process until it exits, or until fdtrace is
+    /// interrupted.
+    pub fn attach(pid: pid_t, options: &TraceOptions) -> anyhow::Result<Self> {
+        let path = std::fs::read_link(format!("/proc/{pid}/exe"))
+            .map_err(|e| anyhow::anyhow!("Failed to find process {pid}: {e}"))?;
+        let fds = fdinfo::snapshot(pid, false);
+
+        // Ctrl-C only stops bpftrace, so that the trace can still be analyzed.
+        ctrlc::set_handler(|| log::info!("Stopping the trace"))?;
+
+        let mut command = Command::new("bpftrace");
+        command.arg("-p").arg(pid.to_string());
+        let (output, ()) = Self::run(command, options, |_| ())?;
+
+        Self::finish(Self::insert_attach(
+            &output,
+            pid,
+            &path.to_string_lossy(),
+            &fds,
+        ))
+    }
+
+    /// Returns the first child process of `pid`, waiting up to a few seconds
+    /// for it to be forked.
+    fn wait_for_child(pid: u32) -> Option<pid_t> {
+        let start = Instant::now();
+        while start.elapsed() < CHILD_TIMEOUT {
+            let tasks = std::fs::read_dir(format!("/proc/{pid}/task")).ok()?;
+            let child = tasks.flatten().find_map(|task| {
+                let children = std::fs::read_to_string(task.path().join("children")).ok()?;
+                children.split_whitespace().next()?.parse().ok()
+            });
+            if child.is_some() {
+                return child;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        None
+    }
+
+    /// Runs bpftrace with the script and returns its output. `spawned` is
+    /// called with the pid of bpftrace once it's started.
+    fn run<T>(
+        mut command: Command, options: &TraceOptions, spawned: impl FnOnce(u32) -> T,
+    ) -> anyhow::Result<(String, T)> {
         let script = {
             let mut file = NamedTempFile::new()?;
-            writeln!(file, "{}", include_str!("../data/fdtrace.bt"))?;
+            writeln!(file, "{}", Self::script(options))?;
             file
         };
 
         let tmpfile = NamedTempFile::new()?;
-        let cmd = std::process::Command::new("bpftrace")
-            .arg("-c")
-            .arg(program)
+        let child = command
             .arg("-o")
             .arg(tmpfile.path())
             .arg(script.path())
-            .output()?;
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let result = spawned(child.id());
 
+        let cmd = child.wait_with_output()?;
         if !cmd.status.success() {
             let error = String::from_utf8_lossy(&cmd.stderr);
             return Err(anyhow::anyhow!("{error}"));
         }
 
-        let output = std::fs::read_to_string(tmpfile)?;
+        Ok((std::fs::read_to_string(tmpfile)?, result))
+    }
+
+    fn finish(output: String) -> anyhow::Result<Self> {
         std::fs::write("raw_trace.txt", &output).unwrap();
 
-        Ok(Self {
-            syscalls: Self::parse_trace(&output)?,
+        let mut syscalls = Self::parse_trace(&output)?;
+        stack::symbolize(&mut syscalls);
+
+        Ok(Self { syscalls })
+    }
+
+    /// Inserts the inherited fds right after the `execve` of the target
+    /// process, with its timestamp, pid and tid.
+    fn insert_inherited_fds(trace: &str, fds: &[FdInfo]) -> String {
+        let mut output = String::with_capacity(trace.len());
+        let mut inserted = false;
+        for line in trace.lines() {
+            output.push_str(line);
+            output.push('\n');
+            if inserted {
+                continue;
+            }
+
+            if let Some(Syscall {
+                ts,
+                pid,
+                tid,
+                raw: RawSyscall::Execve { .. },
+                ..
+            }) = Syscall::from_parts(line)
+            {
+                for fd in fds {
+                    output.push_str(&format!("{ts};{pid};{tid};{}\n", fd.record()));
+                }
+                inserted = true;
+            }
+        }
+
+        output
+    }
+
+    /// Inserts the attached process and its open fds after the header, with
+    /// the timestamp of the first event.
+    fn insert_attach(trace: &str, pid: pid_t, path: &str, fds: &[FdInfo]) -> String {
+        let mut lines = trace.lines();
+        let header = lines.next().unwrap_or_default();
+        let ts = lines
+            .clone()
+            .find_map(Syscall::from_parts)
+            .map(|s| s.ts)
+            .unwrap_or_default();
+
+        let mut output = format!("{header}\n{ts};{pid};{pid};attach;{path}\n");
+        for fd in fds {
+            output.push_str(&format!("{ts};{pid};{pid};{}\n", fd.record()));
+        }
+        for line in lines {
+            output.push_str(line);
+            output.push('\n');
+        }
+
+        output
+    }
+
+    /// Returns the bpftrace script with the `// @filter-*` placeholders
+    /// replaced by the prefix rules of the filter, and the `// @stack-*`
+    /// placeholders replaced by `ustack` records.
+    pub fn script(options: &TraceOptions) -> String {
+        let script = include_str!("../data/fdtrace.bt");
+
+        let skip_path = options.filter.and_then(Self::skip_path);
+        if skip_path.is_none() && options.stacks == Stacks::None {
+            return script.to_string();
+        }
+
+        // The stack is printed before the syscall, so that the exit still
+        // directly follows the syscall of the thread.
+        let stack = r#"printf("%lld;%d;%d;ustack;%s\n%s\n", nsecs, pid, tid, comm, ustack(perf));"#;
+
+        let mut output = String::new();
+        for line in script.lines() {
+            let indent = &line[..line.len() - line.trim_start().len()];
+            let Some((marker, arg)) = line
+                .trim_start()
+                .strip_prefix("// @")
+                .map(|rest| rest.split_once(' ').unwrap_or((rest, "")))
+            else {
+                output.push_str(line);
+                output.push('\n');
+                continue;
+            };
+
+            let code = match marker {
+                "stack-open" => (options.stacks != Stacks::None).then(|| stack.to_string()),
+                "stack-io" => (options.stacks == Stacks::All).then(|| stack.to_string()),
+                _ => skip_path
+                    .as_deref()
+                    .map(|skip_path| Self::filter_code(marker, arg, skip_path)),
+            };
+            match code {
+                Some(code) => output.push_str(&format!("{indent}{code}\n")),
+                None => {
+                    output.push_str(line);
+                    output.push('\n');
+                }
+            }
+        }
+
+        output
+    }
+
+    /// Returns the condition under which the path in `$path` is skipped, or
+    /// `None` if the filter has no prefix rules.
+    fn skip_path(filter: &PathFilter) -> Option<String> {
+        let (include, exclude) = (filter.include_prefixes(), filter.exclude_prefixes());
+        if include.is_empty() && exclude.is_empty() {
+            return None;
+        }
+
+        let matches_any = |prefixes: &[&str]| {
+            prefixes
+                .iter()
+                .map(|prefix| {
+                    let escaped = prefix.replace('\\', "\\\\").replace('"', "\\\"");
+                    format!("strncmp($path, \"{escaped}\", {}) == 0", prefix.len())
+                })
+                .join(" || ")
+        };
+        Some(match (include.is_empty(), exclude.is_empty()) {
+            (true, _) => matches_any(&exclude),
+            (false, true) => format!("!({})", matches_any(&include)),
+            (false, false) => format!("!({}) || {}", matches_any(&include), matches_any(&exclude)),
         })
     }
 
+    /// Returns the code of a `// @filter-*` placeholder.
+    ///
+    /// The opened fds of skipped files are remembered, so that their reads,
+    /// writes and closes (and the exit of the syscalls) can be skipped as well.
+    /// Duplicates of skipped fds are skipped too. Fds can be closed without a
+    /// traced `close` (e.g. `O_CLOEXEC` or the target of `dup2`), so every
+    /// fd that is returned for a file that isn't skipped is forgotten.
+    fn filter_code(marker: &str, arg: &str, skip_path: &str) -> String {
+        match marker {
+            "filter-path" => format!(
+                "$path = str({arg}); if ({skip_path}) {{ @fdtrace_skip[tid] = 1; return; }}"
+            ),
+            "filter-new-fd" => "if (@fdtrace_skip[tid]) { delete(@fdtrace_skip[tid]); if \
+                                (args->ret >= 0) { @fdtrace_skip_fd[pid, (uint64)args->ret] = 1; \
+                                } return; } if (args->ret >= 0) { delete(@fdtrace_skip_fd[pid, \
+                                (uint64)args->ret]); }"
+                .to_string(),
+            "filter-reused-fd" => {
+                format!("if (args->ret >= 0) {{ delete(@fdtrace_skip_fd[pid, (uint64){arg}]); }}")
+            }
+            "filter-fd" => format!(
+                "if (@fdtrace_skip_fd[pid, (uint64){arg}]) {{ @fdtrace_skip[tid] = 1; return; }}"
+            ),
+            "filter-close" => format!(
+                "if (@fdtrace_skip_fd[pid, (uint64){arg}]) {{ delete(@fdtrace_skip_fd[pid, \
+                 (uint64){arg}]); @fdtrace_skip[tid] = 1; return; }}"
+            ),
+            "filter-exit" => {
+                "if (@fdtrace_skip[tid]) { delete(@fdtrace_skip[tid]); return; }".to_string()
+            }
+            "filter-end" => "clear(@fdtrace_skip); clear(@fdtrace_skip_fd);".to_string(),
+            _ => panic!("Unknown placeholder: // @{marker} {arg}"),
+        }
+    }
+
+    /// Loads a trace that was previously recorded with `fdtrace.bt`.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let output = std::fs::read_to_string(path)?;
+
+        let mut syscalls = Self::parse_trace(&output)?;
+        stack::symbolize(&mut syscalls);
+
+        Ok(Self { syscalls })
+    }
+
     pub fn parse_trace(trace: &str) -> anyhow::Result<Vec<Syscall>> {
         let mut target_pid = None;
 
+        // The target process and all of its (transitive) children.
+        let mut process_tree = HashSet::new();
+
+        // Whether the last line was a stack of the target process, whose frames
+        // are printed on the following (indented) lines.
+        let mut in_stack = false;
+
         let mut syscalls = Vec::new();
         for line in trace.lines().skip(1) {
             if line.starts_with("Lost ") {
@@ -46,6 +328,18 @@ impl BpfTracer {
                 continue;
             }
 
+            if line.trim().is_empty() || line.starts_with(char::is_whitespace) {
+                if let Some(RawSyscall::Stack { frames }) = syscalls
+                    .last_mut()
+                    .map(|s: &mut Syscall| &mut s.raw)
+                    .filter(|_| in_stack)
+                {
+                    frames.extend(stack::Frame::parse(line));
+                }
+                continue;
+            }
+            in_stack = false;
+
             let syscall =
                 Syscall::from_parts(line).unwrap_or_else(|| panic!("Failed to parse: {line}"));
 
@@ -53,21 +347,28 @@ impl BpfTracer {
             // want. We need to find the 'execve' syscall to find the process id of our
             // target process.
             //
-            let Some(target_pid) = target_pid else {
-                if let RawSyscall::Execve { path } = &syscall.raw {
+            if target_pid.is_none() {
+                if let RawSyscall::Execve { path } | RawSyscall::Attach { path } = &syscall.raw {
                     log::info!("Target process: {:?}", path);
                     target_pid = Some(syscall.pid);
+                    process_tree.insert(syscall.pid);
+                    syscalls.push(syscall);
                 }
                 continue;
-            };
+            }
 
             // After we have our target_pid, we can filter out all the other logs that
-            // aren't related to this process.
+            // aren't related to this process or its children.
             //
-            if syscall.pid != target_pid {
+            if !process_tree.contains(&syscall.pid) {
                 continue;
             }
+            if let RawSyscall::Fork { child } = syscall.raw {
+                log::info!("Process {} forked: {child}", syscall.pid);
+                process_tree.insert(child);
+            }
 
+            in_stack = matches!(syscall.raw, RawSyscall::Stack { .. });
             syscalls.push(syscall);
         }
 
@@ -99,3 +400,123 @@ impl BpfTracer {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_script_without_filter() {
+        let options = TraceOptions {
+            filter: Some(&PathFilter::default()),
+            ..Default::default()
+        };
+        assert_eq!(
+            BpfTracer::script(&options),
+            include_str!("../data/fdtrace.bt")
+        );
+    }
+
+    #[test]
+    fn test_script_with_filter() {
+        let filter = PathFilter::new(&[], &["/usr/lib/*".into(), "*.so".into()])
+            .unwrap()
+            .exclude_loader();
+        let script = BpfTracer::script(&TraceOptions {
+            filter: Some(&filter),
+            ..Default::default()
+        });
+
+        insta::assert_snapshot!(script);
+    }
+
+    #[test]
+    fn test_script_with_stacks() {
+        let stacks = |stacks| {
+            let script = BpfTracer::script(&TraceOptions {
+                stacks,
+                ..Default::default()
+            });
+            assert!(!script.contains("@fdtrace_skip"));
+            script.matches("ustack(perf)").count()
+        };
+
+        assert_eq!(stacks(Stacks::Open), 2);
+        assert_eq!(stacks(Stacks::All), 11);
+    }
+
+    #[test]
+    fn test_insert_inherited_fds() {
+        let trace = [
+            "Attaching 17 probes...",
+            "1000;5;5;read;3;10;bash",
+            "1100;10;10;execve;/bin/app;bash",
+        ]
+        .map(|line| format!("{line}\n"))
+        .concat();
+        let fds = [
+            FdInfo {
+                fd: 1,
+                path: "pipe:[42]".into(),
+                flags: 1,
+                pos: 0,
+            },
+            FdInfo {
+                fd: 4,
+                path: "/var/log/app.log".into(),
+                flags: 0o2001,
+                pos: 4096,
+            },
+        ];
+        fn records(syscalls: &[Syscall]) -> Vec<(u64, pid_t, pid_t, &RawSyscall)> {
+            syscalls
+                .iter()
+                .map(|s| (s.ts, s.pid, s.tid, &s.raw))
+                .collect()
+        }
+
+        let launched = BpfTracer::insert_inherited_fds(&trace, &fds);
+        let syscalls = BpfTracer::parse_trace(&launched).unwrap();
+        assert_eq!(
+            syscalls[0].raw,
+            RawSyscall::Execve {
+                path: "/bin/app".into()
+            }
+        );
+        assert_eq!(
+            records(&syscalls)[2],
+            (
+                1100,
+                10,
+                10,
+                &RawSyscall::InheritedFd {
+                    fd: 4,
+                    flags: 0o2001,
+                    pos: 4096,
+                    path: "/var/log/app.log".into()
+                }
+            )
+        );
+        assert_eq!(syscalls.len(), 3);
+
+        // The fds of an attached process are recorded before its first event
+        let attached = BpfTracer::insert_attach(&trace, 5, "/bin/bash", &fds);
+        let syscalls = BpfTracer::parse_trace(&attached).unwrap();
+        assert_eq!(
+            records(&syscalls)[1],
+            (
+                1000,
+                5,
+                5,
+                &RawSyscall::InheritedFd {
+                    fd: 1,
+                    flags: 1,
+                    pos: 0,
+                    path: "pipe:[42]".into()
+                }
+            )
+        );
+        assert_eq!(syscalls.len(), 4);
+        assert_eq!(syscalls[3].raw, RawSyscall::Read { fd: 3, count: 10 });
+    }
+}
@@ -0,0 +1,226 @@
+use crate::analysis::{
+    file::{FileInfo, FileSession},
+    thread::ThreadAnalysis,
+    Analysis,
+};
+use itertools::Itertools;
+use ratatui::{
+    crossterm::event::{KeyCode, KeyEvent},
+    widgets::{ListState, TableState},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pane {
+    Threads,
+    Files,
+    Sessions,
+}
+
+impl Pane {
+    const fn next(self) -> Self {
+        match self {
+            Pane::Threads => Pane::Files,
+            Pane::Files => Pane::Sessions,
+            Pane::Sessions => Pane::Threads,
+        }
+    }
+}
+
+/// The column the file list is sorted by (descending).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    Opens,
+    Bytes,
+    Duration,
+    Idle,
+}
+
+impl SortKey {
+    const fn next(self) -> Self {
+        match self {
+            SortKey::Opens => SortKey::Bytes,
+            SortKey::Bytes => SortKey::Duration,
+            SortKey::Duration => SortKey::Idle,
+            SortKey::Idle => SortKey::Opens,
+        }
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            SortKey::Opens => "opens",
+            SortKey::Bytes => "bytes",
+            SortKey::Duration => "duration",
+            SortKey::Idle => "idle",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputMode {
+    Normal,
+    Search,
+}
+
+pub struct App<'a> {
+    pub threads: Vec<&'a ThreadAnalysis>,
+    pub focus: Pane,
+    pub sort: SortKey,
+    pub filter: String,
+    pub input_mode: InputMode,
+    pub should_quit: bool,
+
+    pub thread_state: ListState,
+    pub file_state: TableState,
+    pub session_state: ListState,
+}
+
+impl<'a> App<'a> {
+    pub fn new(analysis: &'a Analysis) -> Self {
+        let threads = analysis.threads().collect::<Vec<_>>();
+
+        let mut app = Self {
+            threads,
+            focus: Pane::Threads,
+            sort: SortKey::Bytes,
+            filter: String::new(),
+            input_mode: InputMode::Normal,
+            should_quit: false,
+            thread_state: ListState::default(),
+            file_state: TableState::default(),
+            session_state: ListState::default(),
+        };
+        app.thread_state
+            .select((!app.threads.is_empty()).then_some(0));
+        app.reset_files();
+
+        app
+    }
+
+    pub fn selected_thread(&self) -> Option<&'a ThreadAnalysis> {
+        self.thread_state
+            .selected()
+            .and_then(|i| self.threads.get(i))
+            .copied()
+    }
+
+    /// Returns the files of the selected thread that match the path filter,
+    /// in the current sort order.
+    pub fn files(&self) -> Vec<(&'a str, &'a FileInfo)> {
+        let Some(thread) = self.selected_thread() else {
+            return Vec::new();
+        };
+
+        let sort = self.sort;
+        thread
+            .files()
+            .iter()
+            .filter(|(path, _)| path.contains(&self.filter))
+            .map(|(path, info)| (path.as_str(), info))
+            .sorted_by(|(a_path, a), (b_path, b)| {
+                let ordering = match sort {
+                    SortKey::Opens => a.sessions.len().cmp(&b.sessions.len()),
+                    SortKey::Bytes => {
+                        let (a_read, a_write) = a.total_bytes();
+                        let (b_read, b_write) = b.total_bytes();
+                        (a_read + a_write).cmp(&(b_read + b_write))
+                    }
+                    SortKey::Duration => a.total_duration().cmp(&b.total_duration()),
+                    SortKey::Idle => a.total_idle_time_ms().total_cmp(&b.total_idle_time_ms()),
+                };
+                ordering.reverse().then_with(|| a_path.cmp(b_path))
+            })
+            .collect()
+    }
+
+    pub fn selected_file(&self) -> Option<(&'a str, &'a FileInfo)> {
+        let index = self.file_state.selected()?;
+        self.files().get(index).copied()
+    }
+
+    pub fn selected_session(&self) -> Option<&'a FileSession> {
+        let (_, info) = self.selected_file()?;
+        info.sessions.get(self.session_state.selected()?)
+    }
+
+    fn reset_files(&mut self) {
+        let has_files = !self.files().is_empty();
+        self.file_state.select(has_files.then_some(0));
+        self.reset_sessions();
+    }
+
+    fn reset_sessions(&mut self) {
+        let has_sessions = self
+            .selected_file()
+            .is_some_and(|(_, info)| !info.sessions.is_empty());
+        self.session_state.select(has_sessions.then_some(0));
+    }
+
+    /// Moves the selection of the focused pane by `delta` rows.
+    fn move_selection(&mut self, delta: isize) {
+        fn step(selected: Option<usize>, len: usize, delta: isize) -> Option<usize> {
+            if len == 0 {
+                return None;
+            }
+            let index = selected.unwrap_or(0).saturating_add_signed(delta);
+            Some(index.min(len - 1))
+        }
+
+        match self.focus {
+            Pane::Threads => {
+                let index = step(self.thread_state.selected(), self.threads.len(), delta);
+                self.thread_state.select(index);
+                self.reset_files();
+            }
+            Pane::Files => {
+                let index = step(self.file_state.selected(), self.files().len(), delta);
+                self.file_state.select(index);
+                self.reset_sessions();
+            }
+            Pane::Sessions => {
+                let len = self
+                    .selected_file()
+                    .map_or(0, |(_, info)| info.sessions.len());
+                let index = step(self.session_state.selected(), len, delta);
+                self.session_state.select(index);
+            }
+        }
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) {
+        if self.input_mode == InputMode::Search {
+            match key.code {
+                KeyCode::Enter => self.input_mode = InputMode::Normal,
+                KeyCode::Esc => {
+                    self.input_mode = InputMode::Normal;
+                    self.filter.clear();
+                }
+                KeyCode::Backspace => {
+                    self.filter.pop();
+                }
+                KeyCode::Char(c) => self.filter.push(c),
+                _ => return,
+            }
+            self.reset_files();
+            return;
+        }
+
+        match key.code {
+            KeyCode::Char('q') => self.should_quit = true,
+            KeyCode::Tab => self.focus = self.focus.next(),
+            KeyCode::Down | KeyCode::Char('j') => self.move_selection(1),
+            KeyCode::Up | KeyCode::Char('k') => self.move_selection(-1),
+            KeyCode::PageDown => self.move_selection(10),
+            KeyCode::PageUp => self.move_selection(-10),
+            KeyCode::Char('s') => {
+                self.sort = self.sort.next();
+                self.reset_files();
+            }
+            KeyCode::Char('/') => self.input_mode = InputMode::Search,
+            KeyCode::Esc => {
+                self.filter.clear();
+                self.reset_files();
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use crate::analysis::Analysis;
+use app::App;
+use ratatui::{
+    crossterm::event::{self, Event, KeyEventKind},
+    DefaultTerminal,
+};
+
+mod app;
+mod ui;
+
+/// Opens the interactive browser for the analysis and blocks until the user
+/// quits.
+pub fn run(analysis: &Analysis) -> anyhow::Result<()> {
+    let mut terminal = ratatui::init();
+    let result = run_app(&mut terminal, App::new(analysis));
+    ratatui::restore();
+
+    result
+}
+
+fn run_app(terminal: &mut DefaultTerminal, mut app: App) -> anyhow::Result<()> {
+    while !app.should_quit {
+        terminal.draw(|frame| ui::draw(frame, &mut app))?;
+
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Press {
+                app.handle_key(key);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tracer::BpfTracer;
+    use ratatui::{
+        backend::TestBackend,
+        crossterm::event::{KeyCode, KeyEvent},
+        Terminal,
+    };
+
+    fn render(app: &mut App) -> String {
+        let mut terminal = Terminal::new(TestBackend::new(120, 40)).unwrap();
+        terminal.draw(|frame| ui::draw(frame, app)).unwrap();
+        terminal.backend().to_string()
+    }
+
+    fn press(app: &mut App, keys: &str) {
+        for c in keys.chars() {
+            app.handle_key(KeyEvent::from(KeyCode::Char(c)));
+        }
+    }
+
+    #[test]
+    fn test_tui_threaded() {
+        let raw_trace = include_str!("../../data/threaded.txt");
+        let analysis = Analysis::new(BpfTracer::parse_trace(raw_trace).unwrap());
+        let mut app = App::new(&analysis);
+
+        insta::assert_snapshot!("tui_threaded", render(&mut app));
+
+        // Sort by opens, then select the second session of the first file
+        press(&mut app, "sss");
+        app.handle_key(KeyEvent::from(KeyCode::Tab));
+        app.handle_key(KeyEvent::from(KeyCode::Tab));
+        press(&mut app, "j");
+        insta::assert_snapshot!("tui_threaded_session", render(&mut app));
+    }
+
+    #[test]
+    fn test_tui_filter() {
+        let raw_trace = include_str!("../../data/threaded.txt");
+        let analysis = Analysis::new(BpfTracer::parse_trace(raw_trace).unwrap());
+        let mut app = App::new(&analysis);
+
+        press(&mut app, "/passwd");
+        app.handle_key(KeyEvent::from(KeyCode::Enter));
+        let files = app.files();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].0, "/etc/passwd");
+
+        app.handle_key(KeyEvent::from(KeyCode::Esc));
+        assert!(app.filter.is_empty());
+        assert!(app.files().len() > 1);
+    }
+
+    #[test]
+    fn test_tui_many_threads() {
+        let mut trace = ["Attaching 13 probes...", "900;10;10;execve;/bin/app"]
+            .map(String::from)
+            .to_vec();
+        for tid in 10..40 {
+            trace.push(format!(
+                "{};10;{tid};openat;4294967196;/etc/hosts;524288",
+                tid * 100
+            ));
+            trace.push(format!("{};10;{tid};openat_exit;3", tid * 100 + 10));
+            trace.push(format!("{};10;{tid};close;3", tid * 100 + 20));
+            trace.push(format!("{};10;{tid};close_exit;0", tid * 100 + 30));
+        }
+        let analysis = crate::analysis::analyze(&trace);
+        let mut app = App::new(&analysis);
+
+        // The timeline keeps its height and scrolls to the selected thread
+        press(&mut app, &"j".repeat(20));
+        let screen = render(&mut app);
+        assert!(screen.contains("Timeline (open files, threads 14-21 of 30)"));
+        assert!(screen.contains("│       30 "));
+        assert!(!screen.contains("│       10 "));
+    }
+}
@@ -0,0 +1,286 @@
+use super::app::{App, InputMode, Pane, SortKey};
+use crate::analysis::{file::FileEvent, thread::ThreadAnalysis, utils};
+use ratatui::{
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::Line,
+    widgets::{Block, List, ListItem, Paragraph, Row, Table},
+    Frame,
+};
+
+const TIMELINE_LEVELS: [char; 9] = [' ', '▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// The most threads shown at once in the timeline, the others are scrolled
+/// to with the selection.
+const TIMELINE_ROWS: usize = 8;
+
+pub fn draw(frame: &mut Frame, app: &mut App) {
+    let [top, detail, timeline, status] = Layout::vertical([
+        Constraint::Percentage(45),
+        Constraint::Min(6),
+        Constraint::Length(app.threads.len().min(TIMELINE_ROWS) as u16 + 2),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+    let [threads, files] =
+        Layout::horizontal([Constraint::Length(26), Constraint::Min(0)]).areas(top);
+    let [sessions, events] =
+        Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)]).areas(detail);
+
+    draw_threads(frame, app, threads);
+    draw_files(frame, app, files);
+    draw_sessions(frame, app, sessions);
+    draw_events(frame, app, events);
+    draw_timeline(frame, app, timeline);
+    draw_status(frame, app, status);
+}
+
+fn block(app: &App, pane: Option<Pane>, title: String) -> Block<'static> {
+    let style = if pane == Some(app.focus) {
+        Style::default().fg(Color::Yellow)
+    } else {
+        Style::default()
+    };
+
+    Block::bordered().title(title).border_style(style)
+}
+
+fn highlight() -> Style {
+    Style::default().add_modifier(Modifier::REVERSED)
+}
+
+fn draw_threads(frame: &mut Frame, app: &mut App, area: Rect) {
+    let items = app
+        .threads
+        .iter()
+        .map(|thread| {
+            let name = thread.name().map(|n| format!(" {n}")).unwrap_or_default();
+            ListItem::new(format!(
+                "{}/{}{name} ({} files)",
+                thread.pid(),
+                thread.tid(),
+                thread.files().len()
+            ))
+        })
+        .collect::<Vec<_>>();
+
+    let list = List::new(items)
+        .block(block(app, Some(Pane::Threads), " Process/Thread ".into()))
+        .highlight_style(highlight());
+    frame.render_stateful_widget(list, area, &mut app.thread_state);
+}
+
+fn draw_files(frame: &mut Frame, app: &mut App, area: Rect) {
+    let header = ["Path", "Opens", "Bytes", "Duration", "Idle"]
+        .into_iter()
+        .zip([
+            None,
+            Some(SortKey::Opens),
+            Some(SortKey::Bytes),
+            Some(SortKey::Duration),
+            Some(SortKey::Idle),
+        ])
+        .map(|(name, key)| {
+            if key == Some(app.sort) {
+                format!("{name} ▼")
+            } else {
+                name.to_string()
+            }
+        });
+
+    let files = app.files();
+    let rows = files.iter().map(|(path, info)| {
+        let (read, write) = info.total_bytes();
+        Row::new(vec![
+            path.to_string(),
+            info.sessions.len().to_string(),
+            (read + write).to_string(),
+            format!("{:.2} ms", info.total_duration_ms()),
+            format!("{:.2} ms", info.total_idle_time_ms()),
+        ])
+    });
+
+    let title = if app.filter.is_empty() {
+        format!(" Files ({}) ", files.len())
+    } else {
+        format!(" Files ({}) matching '{}' ", files.len(), app.filter)
+    };
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Min(20),
+            Constraint::Length(7),
+            Constraint::Length(10),
+            Constraint::Length(13),
+            Constraint::Length(13),
+        ],
+    )
+    .header(Row::new(header).style(Style::default().add_modifier(Modifier::BOLD)))
+    .block(block(app, Some(Pane::Files), title))
+    .row_highlight_style(highlight());
+    frame.render_stateful_widget(table, area, &mut app.file_state);
+}
+
+fn draw_sessions(frame: &mut Frame, app: &mut App, area: Rect) {
+    let sessions = app.selected_file().map(|(_, info)| &info.sessions);
+    let items = sessions
+        .into_iter()
+        .flatten()
+        .enumerate()
+        .map(|(i, session)| {
+            ListItem::new(format!(
+                "Session {}: {:.2} ms (idle {:.2} ms)",
+                i + 1,
+                session.duration_ms(),
+                session.idle_time_ms()
+            ))
+        })
+        .collect::<Vec<_>>();
+
+    let list = List::new(items)
+        .block(block(app, Some(Pane::Sessions), " Sessions ".into()))
+        .highlight_style(highlight());
+    frame.render_stateful_widget(list, area, &mut app.session_state);
+}
+
+fn draw_events(frame: &mut Frame, app: &App, area: Rect) {
+    let mut lines = Vec::new();
+    if let Some(session) = app.selected_session() {
+        let offset = |ts: u64| utils::ns_to_ms(ts.saturating_sub(session.open_ts) as f64);
+
+        lines.push(Line::from(format!(
+            "{:>12.3} ms  Open {}",
+            0.0, session.path
+        )));
+        for event in &session.events {
+            let description = match event {
+                FileEvent::Read { bytes, .. } => format!("Read {bytes} bytes"),
+                FileEvent::Write { bytes, .. } => format!("Write {bytes} bytes"),
+                FileEvent::Transfer {
+                    bytes,
+                    is_source: true,
+                    peer,
+                    ..
+                } => format!("Copy {bytes} bytes to {peer}"),
+                FileEvent::Transfer { bytes, peer, .. } => {
+                    format!("Copy {bytes} bytes from {peer}")
+                }
+                FileEvent::Sync { op, .. } => op.name().to_string(),
+                FileEvent::Truncate { length, .. } => format!("Truncate to {length} bytes"),
+                FileEvent::Allocate { offset, len, .. } => {
+                    format!("Allocate {len} bytes at {offset}")
+                }
+            };
+            lines.push(Line::from(format!(
+                "{:>12.3} ms  {description} in {:.3} ms",
+                offset(event.start_ts()),
+                utils::ns_to_ms((event.end_ts() - event.start_ts()) as f64)
+            )));
+        }
+        lines.push(Line::from(format!(
+            "{:>12.3} ms  Close",
+            offset(session.close_ts)
+        )));
+    }
+
+    let paragraph = Paragraph::new(lines).block(block(app, None, " Events ".into()));
+    frame.render_widget(paragraph, area);
+}
+
+/// Returns the first and last timestamp of all sessions of the threads.
+fn time_span(threads: &[&ThreadAnalysis]) -> Option<(u64, u64)> {
+    let sessions = threads
+        .iter()
+        .flat_map(|t| t.files().values())
+        .flat_map(|info| &info.sessions);
+
+    let start = sessions.clone().map(|s| s.open_ts).min()?;
+    let end = sessions.map(|s| s.close_ts).max()?;
+    Some((start, end.max(start + 1)))
+}
+
+/// Renders one row per thread that shows how many files were open over time,
+/// scrolled so that the selected thread is visible.
+fn draw_timeline(frame: &mut Frame, app: &App, area: Rect) {
+    const LABEL_WIDTH: usize = 10;
+
+    let height = (area.height as usize).saturating_sub(2);
+    let selected = app.thread_state.selected().unwrap_or_default();
+    let skip = selected
+        .saturating_sub(height.saturating_sub(1))
+        .min(app.threads.len().saturating_sub(height));
+    let title = if app.threads.len() > height {
+        format!(
+            " Timeline (open files, threads {}-{} of {}) ",
+            skip + 1,
+            (skip + height).min(app.threads.len()),
+            app.threads.len()
+        )
+    } else {
+        " Timeline (open files) ".into()
+    };
+
+    let block = block(app, None, title);
+    let width = (block.inner(area).width as usize).saturating_sub(LABEL_WIDTH);
+
+    let mut lines = Vec::new();
+    if let Some((start, end)) = time_span(&app.threads).filter(|_| width > 0) {
+        let bucket =
+            |ts: u64| ((ts - start) as u128 * width as u128 / (end - start) as u128) as usize;
+
+        let rows = app
+            .threads
+            .iter()
+            .map(|thread| {
+                let mut counts = vec![0_usize; width];
+                for session in thread.files().values().flat_map(|info| &info.sessions) {
+                    let last = bucket(session.close_ts).min(width - 1);
+                    for count in &mut counts[bucket(session.open_ts).min(last)..=last] {
+                        *count += 1;
+                    }
+                }
+                (thread, counts)
+            })
+            .collect::<Vec<_>>();
+        let max = rows
+            .iter()
+            .flat_map(|(_, counts)| counts.iter().copied())
+            .max()
+            .unwrap_or_default()
+            .max(1);
+
+        // The scale is shared by all threads, so it doesn't change while scrolling
+        for (thread, counts) in rows.into_iter().skip(skip).take(height) {
+            let strip = counts
+                .iter()
+                .map(|&count| {
+                    let level = (count * (TIMELINE_LEVELS.len() - 1)).div_ceil(max);
+                    TIMELINE_LEVELS[level]
+                })
+                .collect::<String>();
+
+            let style = if app.selected_thread().map(|t| t.tid()) == Some(thread.tid()) {
+                Style::default().fg(Color::Yellow)
+            } else {
+                Style::default()
+            };
+            lines.push(
+                Line::from(format!("{:>w$} ", thread.tid(), w = LABEL_WIDTH - 1) + &strip)
+                    .style(style),
+            );
+        }
+    }
+
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+fn draw_status(frame: &mut Frame, app: &App, area: Rect) {
+    let text = match app.input_mode {
+        InputMode::Search => format!("Search: {}_  (enter: apply, esc: clear)", app.filter),
+        InputMode::Normal => format!(
+            "q: quit  tab: switch pane  ↑/↓: move  s: sort ({})  /: search  esc: clear filter",
+            app.sort.name()
+        ),
+    };
+    frame.render_widget(Paragraph::new(text), area);
+}